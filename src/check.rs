@@ -0,0 +1,225 @@
+//! Regression gate: compare a run against a stored baseline and list the changes
+//! that exceed configured thresholds.
+//!
+//! The baseline is a JSON export (`--format json`, see `export`) of a known-good
+//! run, so any export can serve as one and `check --update` writes one. Its last
+//! snapshot is matched against the run exactly like `diff` matches two runs. A
+//! change over its threshold only fails the check when it is significant (see
+//! `diff`); otherwise it is reported as noise.
+
+use crate::diagnostics::Diagnostics;
+use crate::diff::{Delta, DiffReport};
+
+/// Exit code of `check` when a threshold is exceeded (1 is any other error, 2 a
+/// usage error).
+pub const REGRESSION_EXIT_CODE: u8 = 3;
+
+/// Limits on relative growth, in percent of the baseline value.
+#[derive(Debug, Clone, Copy)]
+pub struct Thresholds {
+    /// Total mapped time.
+    pub total_time_pct: f64,
+    /// Time of any matched node...
+    pub node_time_pct: f64,
+    /// ...that also grew by more than this many ms, so tiny nodes do not trip it.
+    pub node_min_ms: f64,
+    /// Total batched_in.
+    pub memory_pct: f64,
+}
+
+/// One change over its threshold.
+#[derive(Debug, Clone)]
+pub struct Violation {
+    /// What grew, e.g. `total mapped time (ms)` or `node Join (stratum 3) time (ms)`.
+    pub subject: String,
+    pub delta: Delta,
+    /// The broken limit, e.g. `+25%`.
+    pub limit: String,
+}
+
+/// Changes over their thresholds, each list with totals first, then nodes by
+/// decreasing absolute growth.
+pub struct Outcome {
+    /// Significant changes: the check fails.
+    pub violations: Vec<Violation>,
+    /// Changes within the worker (or run-to-run) spread.
+    pub noise: Vec<Violation>,
+}
+
+impl Outcome {
+    /// 0 when the check passes, `REGRESSION_EXIT_CODE` when it fails.
+    pub fn exit_code(&self) -> u8 {
+        if self.violations.is_empty() {
+            0
+        } else {
+            REGRESSION_EXIT_CODE
+        }
+    }
+}
+
+/// Compare `report` against `thresholds`. Checks for a metric missing from either
+/// side are skipped with a warning.
+pub fn check(report: &DiffReport, thresholds: &Thresholds, diags: &mut Diagnostics) -> Outcome {
+    let over = |d: &Delta, pct: f64| d.abs > 0.0 && d.rel.is_none_or(|r| r * 100.0 > pct);
+    let mut violations = Vec::new();
+    let mut nodes = Vec::new();
+
+    match &report.totals.total_active_ms {
+        Some(d) => {
+            if over(d, thresholds.total_time_pct) {
+                violations.push(Violation {
+                    subject: "total mapped time (ms)".to_string(),
+                    delta: d.clone(),
+                    limit: format!("+{}%", thresholds.total_time_pct),
+                });
+            }
+            nodes = report
+                .nodes
+                .iter()
+                .filter_map(|n| {
+                    let d = n.deltas.total_active_ms.as_ref()?;
+                    (d.abs > thresholds.node_min_ms && over(d, thresholds.node_time_pct)).then(
+                        || Violation {
+                            subject: format!("node {} ({}) time (ms)", n.label, n.block),
+                            delta: d.clone(),
+                            limit: format!(
+                                "+{}% and +{} ms",
+                                thresholds.node_time_pct, thresholds.node_min_ms
+                            ),
+                        },
+                    )
+                })
+                .collect();
+        }
+        None => diags.warning(
+            None,
+            "time thresholds not checked: the baseline or the run has no time log",
+        ),
+    }
+
+    match &report.totals.batched_in {
+        Some(d) => {
+            if over(d, thresholds.memory_pct) {
+                violations.push(Violation {
+                    subject: "total batched_in".to_string(),
+                    delta: d.clone(),
+                    limit: format!("+{}%", thresholds.memory_pct),
+                });
+            }
+        }
+        None => diags.warning(
+            None,
+            "memory threshold not checked: the baseline or the run has no memory log",
+        ),
+    }
+
+    nodes.sort_by(|a, b| b.delta.abs.total_cmp(&a.delta.abs));
+    violations.extend(nodes);
+    let (violations, noise) = violations
+        .into_iter()
+        .partition(|v| v.delta.is_significant());
+    Outcome { violations, noise }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::{self, Metrics, NodeSummary, RunSummary};
+    use crate::stats::Sample;
+    use pretty_assertions::assert_eq;
+
+    const THRESHOLDS: Thresholds = Thresholds {
+        total_time_pct: 10.0,
+        node_time_pct: 20.0,
+        node_min_ms: 1.0,
+        memory_pct: 10.0,
+    };
+
+    /// Metrics with mean time `ms` and variance `var` over 8 workers.
+    fn metrics(ms: f64, var: f64) -> Metrics {
+        Metrics {
+            total_active_ms: Sample {
+                mean: ms,
+                var,
+                n: 8,
+            },
+            ..Metrics::default()
+        }
+    }
+
+    /// A time-only run with nodes `(label, ms, var)`, whose mapped total is their sum.
+    fn run(nodes: &[(&str, f64, f64)]) -> RunSummary {
+        RunSummary {
+            label: "t0".to_string(),
+            has_time: true,
+            has_memory: false,
+            totals: metrics(nodes.iter().map(|n| n.1).sum(), 1.0),
+            blocks: Default::default(),
+            rules: Default::default(),
+            nodes: nodes
+                .iter()
+                .enumerate()
+                .map(|(i, (label, ms, var))| NodeSummary {
+                    name: i.to_string(),
+                    label: label.to_string(),
+                    block: "stratum 0".to_string(),
+                    fingerprint: None,
+                    metrics: metrics(*ms, *var),
+                    operators: Vec::new(),
+                })
+                .collect(),
+        }
+    }
+
+    fn outcome(base: &[(&str, f64, f64)], new: &[(&str, f64, f64)]) -> Outcome {
+        let report = diff::diff_reports(&run(base), &run(new), 0.95);
+        check(&report, &THRESHOLDS, &mut Diagnostics::discard())
+    }
+
+    fn subjects(violations: &[Violation]) -> Vec<&str> {
+        violations.iter().map(|v| v.subject.as_str()).collect()
+    }
+
+    #[test]
+    fn growth_at_the_limits_passes() {
+        // Total +10%, node +20%: both exactly at their limits.
+        let outcome = outcome(
+            &[("Join", 50.0, 0.1), ("Map", 50.0, 0.1)],
+            &[("Join", 60.0, 0.1), ("Map", 50.0, 0.1)],
+        );
+        assert!(outcome.violations.is_empty());
+        assert!(outcome.noise.is_empty());
+        assert_eq!(outcome.exit_code(), 0);
+    }
+
+    #[test]
+    fn a_slower_run_fails_with_the_regressed_nodes() {
+        // Join +60% (30 ms), Map +4% (under its limit), Scan +100% but within its
+        // worker spread, Tiny +100% but by under 1 ms.
+        let outcome = outcome(
+            &[
+                ("Join", 50.0, 0.1),
+                ("Map", 50.0, 0.1),
+                ("Scan", 10.0, 400.0),
+                ("Tiny", 0.5, 0.0),
+            ],
+            &[
+                ("Join", 80.0, 0.1),
+                ("Map", 52.0, 0.1),
+                ("Scan", 20.0, 400.0),
+                ("Tiny", 1.0, 0.0),
+            ],
+        );
+        assert_eq!(
+            subjects(&outcome.violations),
+            vec!["total mapped time (ms)", "node Join (stratum 0) time (ms)"]
+        );
+        assert_eq!(outcome.violations[1].limit, "+20% and +1 ms");
+        assert_eq!(
+            subjects(&outcome.noise),
+            vec!["node Scan (stratum 0) time (ms)"]
+        );
+        assert_eq!(outcome.exit_code(), REGRESSION_EXIT_CODE);
+        assert_eq!(REGRESSION_EXIT_CODE, 3);
+    }
+}
@@ -0,0 +1,498 @@
+//! Datalog rule text (`RawRule.text`) parsed into a small AST, and the relation
+//! dependency graph built from it.
+//!
+//! Rule shape, as FlowLog prints it:
+//!
+//!   head(x, y) :- a(x, z), !b(z), c(z, y), y != 3.
+//!
+//! Body literals are atoms, negated atoms (`!atom`) and comparisons
+//! (`==`, `!=`/`≠`, `<`, `<=`/`≤`, `>`, `>=`/`≥`). Arguments that are neither variables nor
+//! constants (aggregates such as `min(x)`, arithmetic) are kept as raw text.
+
+use crate::diagnostics::Diagnostics;
+use crate::ops::RuleSpec;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub head: Atom,
+    pub body: Vec<Literal>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Atom {
+    pub relation: String,
+    pub args: Vec<Term>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Term {
+    Var(String),
+    /// `_`
+    Wildcard,
+    /// Number or quoted string, as written.
+    Const(String),
+    /// Anything else (aggregate, arithmetic), as written.
+    Expr(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Atom(Atom),
+    Negated(Atom),
+    Compare { left: Term, op: CmpOp, right: Term },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// Longest first, so `<=` is not read as `<`. FlowLog prints `≠`, `≤` and `≥`.
+const CMP_OPS: [(&str, CmpOp); 9] = [
+    ("==", CmpOp::Eq),
+    ("!=", CmpOp::Ne),
+    ("<=", CmpOp::Le),
+    (">=", CmpOp::Ge),
+    ("≠", CmpOp::Ne),
+    ("≤", CmpOp::Le),
+    ("≥", CmpOp::Ge),
+    ("<", CmpOp::Lt),
+    (">", CmpOp::Gt),
+];
+
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Term::Var(s) | Term::Const(s) | Term::Expr(s) => f.write_str(s),
+            Term::Wildcard => f.write_str("_"),
+        }
+    }
+}
+
+impl fmt::Display for Atom {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let args: Vec<String> = self.args.iter().map(Term::to_string).collect();
+        write!(f, "{}({})", self.relation, args.join(", "))
+    }
+}
+
+/// Parse one rule. A fact (`head(1, 2).`) has an empty body.
+pub fn parse_rule(text: &str) -> Result<Rule, String> {
+    let text = text.trim();
+    let text = text.strip_suffix('.').unwrap_or(text);
+    let (head, body) = match find_top_level(text, ":-") {
+        Some(pos) => (&text[..pos], Some(&text[pos + 2..])),
+        None => (text, None),
+    };
+    let head = parse_atom(head)?;
+    let body = match body {
+        Some(body) => split_top_level(body, ',')?
+            .into_iter()
+            .map(parse_literal)
+            .collect::<Result<_, _>>()?,
+        None => Vec::new(),
+    };
+    Ok(Rule { head, body })
+}
+
+fn parse_literal(text: &str) -> Result<Literal, String> {
+    let text = text.trim();
+    if let Some(atom) = text.strip_prefix('!') {
+        return Ok(Literal::Negated(parse_atom(atom)?));
+    }
+    for (pos, _) in text.char_indices() {
+        if depth_at(text, pos) != 0 {
+            continue;
+        }
+        if let Some((sym, op)) = CMP_OPS.iter().find(|(sym, _)| text[pos..].starts_with(sym)) {
+            let (left, right) = (&text[..pos], &text[pos + sym.len()..]);
+            if left.trim().is_empty() || right.trim().is_empty() {
+                return Err(format!("comparison `{}` is missing an operand", text));
+            }
+            return Ok(Literal::Compare {
+                left: parse_term(left),
+                op: *op,
+                right: parse_term(right),
+            });
+        }
+    }
+    Ok(Literal::Atom(parse_atom(text)?))
+}
+
+fn parse_atom(text: &str) -> Result<Atom, String> {
+    let text = text.trim();
+    let (relation, args) = match text.find('(') {
+        Some(open) => {
+            let args = text[open + 1..]
+                .strip_suffix(')')
+                .ok_or_else(|| format!("atom `{}` does not end with `)`", text))?;
+            (text[..open].trim(), args)
+        }
+        None => (text, ""),
+    };
+    if !is_identifier(relation) {
+        return Err(format!("`{}` is not an atom", text));
+    }
+    let args = if args.trim().is_empty() {
+        Vec::new()
+    } else {
+        split_top_level(args, ',')?
+            .into_iter()
+            .map(parse_term)
+            .collect()
+    };
+    Ok(Atom {
+        relation: relation.to_string(),
+        args,
+    })
+}
+
+fn parse_term(text: &str) -> Term {
+    let text = text.trim();
+    if text == "_" {
+        Term::Wildcard
+    } else if is_identifier(text) {
+        Term::Var(text.to_string())
+    } else if text.parse::<f64>().is_ok()
+        || (text.len() >= 2 && text.starts_with('"') && text.ends_with('"'))
+    {
+        Term::Const(text.to_string())
+    } else {
+        Term::Expr(text.to_string())
+    }
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Parenthesis depth just before byte `pos`, ignoring quoted strings; `-1` when
+/// `pos` is inside a string.
+fn depth_at(text: &str, pos: usize) -> i32 {
+    let (mut depth, mut quoted) = (0, false);
+    for c in text[..pos].chars() {
+        match c {
+            '"' => quoted = !quoted,
+            '(' if !quoted => depth += 1,
+            ')' if !quoted => depth -= 1,
+            _ => {}
+        }
+    }
+    if quoted { -1 } else { depth }
+}
+
+fn find_top_level(text: &str, pat: &str) -> Option<usize> {
+    text.match_indices(pat)
+        .map(|(pos, _)| pos)
+        .find(|&pos| depth_at(text, pos) == 0)
+}
+
+/// Split on `sep` outside parentheses and quotes.
+fn split_top_level(text: &str, sep: char) -> Result<Vec<&str>, String> {
+    let (mut parts, mut start, mut depth, mut quoted) = (Vec::new(), 0, 0i32, false);
+    for (pos, c) in text.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '(' if !quoted => depth += 1,
+            ')' if !quoted => {
+                depth -= 1;
+                if depth < 0 {
+                    return Err(format!("unbalanced `)` in `{}`", text.trim()));
+                }
+            }
+            c if c == sep && !quoted && depth == 0 => {
+                parts.push(&text[start..pos]);
+                start = pos + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    if depth != 0 || quoted {
+        return Err(format!(
+            "unbalanced parentheses or quotes in `{}`",
+            text.trim()
+        ));
+    }
+    parts.push(&text[start..]);
+    Ok(parts)
+}
+
+// ---------------------------------------------------------------------------
+// Relation graph
+// ---------------------------------------------------------------------------
+
+/// Relations of a program and how they depend on each other, from the parsed rules.
+#[derive(Debug, Clone, Default)]
+pub struct RelationGraph {
+    /// Parsed rule per ops.json rule (same index); `None` where the text did not parse.
+    pub rules: Vec<Option<Rule>>,
+    /// Every relation that appears in a head or a body.
+    pub relations: BTreeMap<String, Relation>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Relation {
+    /// Indices of the rules deriving this relation; empty for an input (EDB) relation.
+    pub rules: Vec<usize>,
+    /// Relations used in the bodies of those rules.
+    pub depends_on: BTreeSet<String>,
+    /// The subset of `depends_on` that is used under negation.
+    pub negated: BTreeSet<String>,
+}
+
+impl Relation {
+    /// Derived by at least one rule (an IDB relation).
+    pub fn is_derived(&self) -> bool {
+        !self.rules.is_empty()
+    }
+}
+
+impl RelationGraph {
+    /// Parse every rule text. A rule that does not parse is reported as a warning
+    /// and left out of the graph; the rest of the report does not depend on it.
+    pub fn build(rules: &[RuleSpec], diags: &mut Diagnostics) -> Self {
+        let mut graph = Self::default();
+        for (idx, spec) in rules.iter().enumerate() {
+            let rule = match parse_rule(&spec.text) {
+                Ok(rule) => rule,
+                Err(reason) => {
+                    diags.warning(
+                        None,
+                        format!(
+                            "ops.json rule {} is not parseable Datalog ({}); it is left out of the relation rollup: {}",
+                            idx, reason, spec.text
+                        ),
+                    );
+                    graph.rules.push(None);
+                    continue;
+                }
+            };
+
+            let mut depends_on = BTreeSet::new();
+            let mut negated = BTreeSet::new();
+            for lit in &rule.body {
+                match lit {
+                    Literal::Atom(a) => {
+                        depends_on.insert(a.relation.clone());
+                    }
+                    Literal::Negated(a) => {
+                        depends_on.insert(a.relation.clone());
+                        negated.insert(a.relation.clone());
+                    }
+                    Literal::Compare { .. } => {}
+                }
+            }
+            for relation in &depends_on {
+                graph.relations.entry(relation.clone()).or_default();
+            }
+            let head = graph
+                .relations
+                .entry(rule.head.relation.clone())
+                .or_default();
+            head.rules.push(idx);
+            head.depends_on.extend(depends_on);
+            head.negated.extend(negated);
+            graph.rules.push(Some(rule));
+        }
+        graph
+    }
+
+    /// All relations `relation` depends on, directly or transitively.
+    pub fn upstream(&self, relation: &str) -> BTreeSet<&str> {
+        let mut seen = BTreeSet::new();
+        let mut stack = vec![relation];
+        while let Some(r) = stack.pop() {
+            for dep in self
+                .relations
+                .get(r)
+                .into_iter()
+                .flat_map(|r| &r.depends_on)
+            {
+                if seen.insert(dep.as_str()) {
+                    stack.push(dep);
+                }
+            }
+        }
+        seen
+    }
+
+    /// Input (EDB) relations that feed `relation`, directly or transitively.
+    pub fn inputs(&self, relation: &str) -> BTreeSet<&str> {
+        self.upstream(relation)
+            .into_iter()
+            .filter(|r| self.relations.get(*r).is_some_and(|r| !r.is_derived()))
+            .collect()
+    }
+
+    /// Whether `relation` depends on itself (directly or through other relations).
+    pub fn is_recursive(&self, relation: &str) -> bool {
+        self.upstream(relation).contains(relation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn var(name: &str) -> Term {
+        Term::Var(name.to_string())
+    }
+
+    fn atom(relation: &str, args: &[&str]) -> Atom {
+        Atom {
+            relation: relation.to_string(),
+            args: args.iter().map(|a| parse_term(a)).collect(),
+        }
+    }
+
+    /// The single comparison in the body of `h(x) :- a(x), x <op> 3.`.
+    fn comparison(op: &str) -> (Term, CmpOp, Term) {
+        let rule = parse_rule(&format!("h(x) :- a(x), x {} 3.", op)).unwrap();
+        match &rule.body[1] {
+            Literal::Compare { left, op, right } => (left.clone(), *op, right.clone()),
+            other => panic!("expected a comparison, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_head_and_body_atoms() {
+        let rule = parse_rule("path(x, y) :- edge(x, z), path(z, y).").unwrap();
+        assert_eq!(rule.head, atom("path", &["x", "y"]));
+        assert_eq!(
+            rule.body,
+            vec![
+                Literal::Atom(atom("edge", &["x", "z"])),
+                Literal::Atom(atom("path", &["z", "y"])),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_a_fact_with_an_empty_body() {
+        let rule = parse_rule("edge(1, \"a\").").unwrap();
+        assert_eq!(
+            rule.head.args,
+            vec![Term::Const("1".into()), Term::Const("\"a\"".into())]
+        );
+        assert!(rule.body.is_empty());
+    }
+
+    #[test]
+    fn parses_each_comparison_operator() {
+        let cases = [
+            ("==", CmpOp::Eq),
+            ("!=", CmpOp::Ne),
+            ("≠", CmpOp::Ne),
+            ("<=", CmpOp::Le),
+            ("≤", CmpOp::Le),
+            (">=", CmpOp::Ge),
+            ("≥", CmpOp::Ge),
+            ("<", CmpOp::Lt),
+            (">", CmpOp::Gt),
+        ];
+        for (sym, expected) in cases {
+            assert_eq!(
+                comparison(sym),
+                (var("x"), expected, Term::Const("3".into())),
+                "operator {}",
+                sym
+            );
+        }
+    }
+
+    #[test]
+    fn parses_negated_atoms() {
+        let rule = parse_rule("h(x) :- a(x), !b(x, _).").unwrap();
+        assert_eq!(
+            rule.body[1],
+            Literal::Negated(Atom {
+                relation: "b".into(),
+                args: vec![var("x"), Term::Wildcard],
+            })
+        );
+    }
+
+    #[test]
+    fn keeps_aggregates_and_arithmetic_as_expressions() {
+        let rule = parse_rule("h(x, min(y)) :- a(x, y), x + 1 < y.").unwrap();
+        assert_eq!(rule.head.args[1], Term::Expr("min(y)".into()));
+        assert_eq!(
+            rule.body[1],
+            Literal::Compare {
+                left: Term::Expr("x + 1".into()),
+                op: CmpOp::Lt,
+                right: var("y"),
+            }
+        );
+    }
+
+    #[test]
+    fn ignores_separators_inside_strings() {
+        let rule = parse_rule("h(x) :- a(x, \"a, b :- c\").").unwrap();
+        assert_eq!(rule.body.len(), 1);
+        assert_eq!(
+            rule.body[0],
+            Literal::Atom(Atom {
+                relation: "a".into(),
+                args: vec![var("x"), Term::Const("\"a, b :- c\"".into())],
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_rules() {
+        for text in [
+            "h(x :- a(x).",
+            "h(x) :- a(x)).",
+            "h(x) :- a(x, \"y).",
+            "h(x) :- x < .",
+            "h(x) :- == 3.",
+            "1(x) :- a(x).",
+            "h(x) :- a(x",
+        ] {
+            assert!(parse_rule(text).is_err(), "accepted {}", text);
+        }
+    }
+
+    #[test]
+    fn builds_the_relation_graph() {
+        let specs: Vec<RuleSpec> = [
+            "path(x, y) :- edge(x, y).",
+            "path(x, y) :- path(x, z), edge(z, y).",
+            "unreached(x) :- node(x), !path(0, x).",
+            "not datalog",
+        ]
+        .iter()
+        .map(|text| RuleSpec {
+            text: text.to_string(),
+            root: String::new(),
+            nodes: BTreeMap::new(),
+        })
+        .collect();
+        let graph = RelationGraph::build(&specs, &mut Diagnostics::discard());
+
+        assert!(graph.rules[3].is_none());
+        assert_eq!(graph.relations["path"].rules, vec![0, 1]);
+        assert!(!graph.relations["edge"].is_derived());
+        assert!(graph.is_recursive("path"));
+        assert!(!graph.is_recursive("unreached"));
+        assert_eq!(
+            graph.relations["unreached"].negated,
+            BTreeSet::from(["path".to_string()])
+        );
+        assert_eq!(graph.inputs("unreached"), BTreeSet::from(["edge", "node"]));
+    }
+}
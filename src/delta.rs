@@ -0,0 +1,241 @@
+//! Interval (delta) view of cumulative snapshots.
+//!
+//! Timestamped logs hold cumulative counters at each checkpoint. The interval view of
+//! snapshot `tN` subtracts the previous snapshot's per-worker counters, so the report
+//! can show what happened between two checkpoints. The first snapshot's interval is
+//! the snapshot itself (counted from zero).
+
+use crate::addr::Addr;
+use crate::diagnostics::Diagnostics;
+use crate::log::{MemoryIndex, MemoryRow, Snapshot, TimeIndex, TimeRow};
+use crate::stats::Stats;
+
+use std::collections::BTreeMap;
+
+/// Interval snapshots of the time log, one per input snapshot (same labels).
+pub fn time_intervals(
+    snapshots: &[Snapshot<TimeIndex>],
+    diags: &mut Diagnostics,
+) -> Vec<Snapshot<TimeIndex>> {
+    intervals(snapshots, "time", diags, |iv, addr, row, prev| {
+        let activations = iv.delta(
+            addr,
+            "activations",
+            &row.activations_by_worker,
+            prev.map(|p| p.activations_by_worker.as_slice()),
+        );
+        let ms = iv.delta(
+            addr,
+            "total_active_ms",
+            &row.total_active_ms_by_worker,
+            prev.map(|p| p.total_active_ms_by_worker.as_slice()),
+        );
+        TimeRow {
+            activations: Stats::from_values(&activations),
+            total_active_ms: Stats::from_values(&ms),
+            activations_by_worker: activations,
+            total_active_ms_by_worker: ms,
+            ..row.clone()
+        }
+    })
+}
+
+/// Interval snapshots of the memory log, one per input snapshot (same labels).
+pub fn memory_intervals(
+    snapshots: &[Snapshot<MemoryIndex>],
+    diags: &mut Diagnostics,
+) -> Vec<Snapshot<MemoryIndex>> {
+    intervals(snapshots, "memory", diags, |iv, addr, row, prev| {
+        let mut d = |field: &str, get: fn(&MemoryRow) -> &[f64]| {
+            iv.delta(addr, field, get(row), prev.map(get))
+        };
+        let batched_in = d("batched_in", |r| &r.batched_in_by_worker);
+        let merges = d("merges", |r| &r.merges_by_worker);
+        let merge_in = d("merge_in", |r| &r.merge_in_by_worker);
+        let merge_out = d("merge_out", |r| &r.merge_out_by_worker);
+        let dropped = d("dropped", |r| &r.dropped_by_worker);
+        MemoryRow {
+            batched_in: Stats::from_values(&batched_in),
+            merges: Stats::from_values(&merges),
+            merge_in: Stats::from_values(&merge_in),
+            merge_out: Stats::from_values(&merge_out),
+            dropped: Stats::from_values(&dropped),
+            batched_in_by_worker: batched_in,
+            merges_by_worker: merges,
+            merge_in_by_worker: merge_in,
+            merge_out_by_worker: merge_out,
+            dropped_by_worker: dropped,
+            ..row.clone()
+        }
+    })
+}
+
+/// Build interval snapshots with `row_delta`, reporting counter resets per snapshot.
+///
+/// Extra (header-mapped) columns are carried over unchanged: they are not known to
+/// be cumulative counters (e.g. a `max_batch_ms` column).
+fn intervals<R>(
+    snapshots: &[Snapshot<BTreeMap<Addr, R>>],
+    kind: &str,
+    diags: &mut Diagnostics,
+    row_delta: impl Fn(&mut Interval<'_>, &Addr, &R, Option<&R>) -> R,
+) -> Vec<Snapshot<BTreeMap<Addr, R>>> {
+    let mut out = Vec::with_capacity(snapshots.len());
+    for (i, cur) in snapshots.iter().enumerate() {
+        let prev = i.checked_sub(1).map(|p| &snapshots[p]);
+        let mut iv = Interval {
+            cur_workers: &cur.workers,
+            prev_workers: prev.map(|p| p.workers.as_slice()).unwrap_or_default(),
+            resets: 0,
+            first_reset: None,
+        };
+        let data = cur
+            .data
+            .iter()
+            .map(|(addr, row)| {
+                let prev_row = prev.and_then(|p| p.data.get(addr));
+                (addr.clone(), row_delta(&mut iv, addr, row, prev_row))
+            })
+            .collect();
+
+        if let (Some(prev), Some(example)) = (prev, &iv.first_reset) {
+            diags.warning(
+                None,
+                format!(
+                    "snapshot {}: {} {} counter(s) decreased since {} (counter reset?), e.g. {}; \
+                     the interval view uses the new reading for those",
+                    cur.label, iv.resets, kind, prev.label, example
+                ),
+            );
+        }
+
+        out.push(Snapshot {
+            label: cur.label.clone(),
+            workers: cur.workers.clone(),
+            data,
+        });
+    }
+    out
+}
+
+/// Differencing context for one pair of consecutive snapshots.
+struct Interval<'a> {
+    cur_workers: &'a [u32],
+    prev_workers: &'a [u32],
+    resets: usize,
+    first_reset: Option<String>,
+}
+
+impl Interval<'_> {
+    /// `cur - prev` per worker, matching workers by id; a worker (or operator) missing
+    /// from the previous snapshot counts from zero. A decrease means the counter was
+    /// reset, so the interval value is the new reading itself.
+    fn delta(&mut self, addr: &Addr, field: &str, cur: &[f64], prev: Option<&[f64]>) -> Vec<f64> {
+        cur.iter()
+            .zip(self.cur_workers)
+            .map(|(&v, worker)| {
+                let before = prev
+                    .and_then(|p| {
+                        let pos = self.prev_workers.binary_search(worker).ok()?;
+                        p.get(pos).copied()
+                    })
+                    .unwrap_or(0.0);
+                if v >= before {
+                    return v - before;
+                }
+                self.resets += 1;
+                if self.first_reset.is_none() {
+                    self.first_reset = Some(format!(
+                        "{} of addr {:?} on worker {} ({} -> {})",
+                        field, addr.0, worker, before, v
+                    ));
+                }
+                v
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::Severity;
+    use pretty_assertions::assert_eq;
+
+    /// A time snapshot with one operator at `[0, 1]`: `ms` per worker of `workers`.
+    fn snapshot(label: &str, workers: &[u32], ms: &[f64]) -> Snapshot<TimeIndex> {
+        let ones = vec![1.0; workers.len()];
+        let row = TimeRow {
+            activations: Stats::from_values(&ones),
+            total_active_ms: Stats::from_values(ms),
+            activations_by_worker: ones,
+            total_active_ms_by_worker: ms.to_vec(),
+            extra: BTreeMap::new(),
+            op_name: "Map".to_string(),
+            num_workers: workers.len(),
+        };
+        Snapshot {
+            label: label.to_string(),
+            workers: workers.to_vec(),
+            data: BTreeMap::from([(Addr::new(vec![0, 1]), row)]),
+        }
+    }
+
+    /// Interval `total_active_ms` per worker of each snapshot, and the diagnostics.
+    fn interval_ms(snapshots: &[Snapshot<TimeIndex>]) -> (Vec<Vec<f64>>, Diagnostics) {
+        let mut diags = Diagnostics::discard();
+        let ms = time_intervals(snapshots, &mut diags)
+            .iter()
+            .map(|s| {
+                s.data[&Addr::new(vec![0, 1])]
+                    .total_active_ms_by_worker
+                    .clone()
+            })
+            .collect();
+        (ms, diags)
+    }
+
+    #[test]
+    fn intervals_subtract_the_previous_snapshot() {
+        let (ms, diags) = interval_ms(&[
+            snapshot("t0", &[0, 1], &[2.0, 3.0]),
+            snapshot("t1", &[0, 1], &[5.0, 3.0]),
+        ]);
+        assert_eq!(ms, vec![vec![2.0, 3.0], vec![3.0, 0.0]]);
+        assert!(diags.items().is_empty());
+    }
+
+    #[test]
+    fn a_decrease_is_a_reset_and_keeps_the_new_reading() {
+        let (ms, diags) = interval_ms(&[
+            snapshot("t0", &[0, 1], &[2.0, 3.0]),
+            snapshot("t1", &[0, 1], &[5.0, 1.0]),
+        ]);
+        assert_eq!(ms[1], vec![3.0, 1.0]);
+
+        let warnings: Vec<_> = diags
+            .items()
+            .iter()
+            .map(|d| (d.severity, d.message.as_str()))
+            .collect();
+        assert_eq!(
+            warnings,
+            vec![(
+                Severity::Warning,
+                "snapshot t1: 1 time counter(s) decreased since t0 (counter reset?), e.g. \
+                 total_active_ms of addr [0, 1] on worker 1 (3 -> 1); the interval view uses \
+                 the new reading for those"
+            )]
+        );
+    }
+
+    #[test]
+    fn a_worker_new_in_the_later_snapshot_counts_from_zero() {
+        let (ms, diags) = interval_ms(&[
+            snapshot("t0", &[0, 2], &[2.0, 3.0]),
+            snapshot("t1", &[0, 1, 2], &[5.0, 4.0, 3.5]),
+        ]);
+        assert_eq!(ms[1], vec![3.0, 4.0, 0.5]);
+        assert!(diags.items().is_empty());
+    }
+}
@@ -1,6 +1,11 @@
 //! Shared diagnostics helpers for consistent, colored output.
 
+use crate::Result;
+
+use anyhow::anyhow;
 use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::fmt;
 
 /// Format a warning message with a colored prefix.
 pub fn warn(message: impl AsRef<str>) {
@@ -11,3 +16,147 @@ pub fn warn(message: impl AsRef<str>) {
 pub fn error_message(message: impl AsRef<str>) -> String {
     format!("{} {}", "ERROR".red().bold(), message.as_ref())
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// One problem found while reading inputs, with its source location when known.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub file: Option<String>,
+    pub line: Option<usize>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.file, self.line) {
+            (Some(file), Some(line)) => write!(f, "{}:{}: {}", file, line, self.message),
+            (Some(file), None) => write!(f, "{}: {}", file, self.message),
+            _ => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Source location of a diagnostic: a file, optionally with a 1-based line number.
+#[derive(Debug, Clone, Copy)]
+pub struct Location<'a> {
+    pub file: &'a str,
+    pub line: Option<usize>,
+}
+
+impl<'a> Location<'a> {
+    pub fn file(file: &'a str) -> Self {
+        Self { file, line: None }
+    }
+
+    pub fn line(file: &'a str, line: usize) -> Self {
+        Self {
+            file,
+            line: Some(line),
+        }
+    }
+}
+
+/// Collects diagnostics for one run.
+///
+/// In strict mode (the default) an error aborts immediately. In lenient mode the
+/// error is recorded and `error` returns `Ok`, so the caller can skip the bad input
+/// (a line, an edge, a rule, ...) and keep going.
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    lenient: bool,
+    quiet: bool,
+    items: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    pub fn new(lenient: bool) -> Self {
+        Self {
+            lenient,
+            quiet: false,
+            items: Vec::new(),
+        }
+    }
+
+    /// A lenient collector that prints nothing, for re-running a pass whose problems
+    /// were already reported (e.g. building the interval view of the same snapshots).
+    /// Its items are meant to be dropped.
+    pub fn discard() -> Self {
+        Self {
+            lenient: true,
+            quiet: true,
+            items: Vec::new(),
+        }
+    }
+
+    /// Report an error: fails in strict mode, records it in lenient mode.
+    pub fn error(
+        &mut self,
+        location: Option<Location<'_>>,
+        message: impl Into<String>,
+    ) -> Result<()> {
+        let diag = Self::make(Severity::Error, location, message.into());
+        if !self.lenient {
+            return Err(anyhow!(error_message(diag.to_string())));
+        }
+        self.items.push(diag);
+        Ok(())
+    }
+
+    /// Report a warning: printed to stderr right away and recorded.
+    pub fn warning(&mut self, location: Option<Location<'_>>, message: impl Into<String>) {
+        let diag = Self::make(Severity::Warning, location, message.into());
+        if !self.quiet {
+            warn(diag.to_string());
+        }
+        self.items.push(diag);
+    }
+
+    pub fn items(&self) -> &[Diagnostic] {
+        &self.items
+    }
+
+    /// Print the final stderr summary: every recorded error, plus the warning count
+    /// (warnings were already printed as they happened).
+    pub fn print_summary(&self) {
+        let errors: Vec<&Diagnostic> = self
+            .items
+            .iter()
+            .filter(|d| d.severity == Severity::Error)
+            .collect();
+        let warnings = self.items.len() - errors.len();
+        if self.items.is_empty() {
+            return;
+        }
+
+        // Strict runs abort on the first error, so only lenient runs have any.
+        if self.lenient {
+            eprintln!(
+                "{} {} error(s) skipped in lenient mode, {} warning(s)",
+                "SUMMARY".cyan().bold(),
+                errors.len(),
+                warnings
+            );
+        } else {
+            eprintln!("{} {} warning(s)", "SUMMARY".cyan().bold(), warnings);
+        }
+        for diag in errors {
+            eprintln!("  {} {}", "ERROR".red().bold(), diag);
+        }
+    }
+
+    fn make(severity: Severity, location: Option<Location<'_>>, message: String) -> Diagnostic {
+        Diagnostic {
+            severity,
+            message,
+            file: location.map(|l| l.file.to_string()),
+            line: location.and_then(|l| l.line),
+        }
+    }
+}
@@ -0,0 +1,646 @@
+//! Comparison of two runs (e.g. before and after a planner change).
+//!
+//! Node names are ids from each run's ops.json, so they are not comparable across
+//! runs. Nodes are matched by fingerprint within the same block, falling back to
+//! (label, block); operators are matched by op_name within matched nodes. Rules are
+//! matched by text within the block of their plan root, and strata by block name.
+//! Values are means over workers, as everywhere else in the report.
+//!
+//! Each delta is tested for significance with Welch's t-test over the per-worker
+//! values of both sides, so a change smaller than the worker spread is reported as
+//! noise, with a confidence interval of the difference. A side averaged from
+//! repeated runs is tested over its runs instead, for totals, strata and nodes.
+//!
+//! Both sides are reduced to a `RunSummary` first.
+
+use crate::runs::RunMetrics;
+use crate::stats::{self, Sample, Significance, Stats};
+use crate::view::ReportData;
+
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+// ---------------------------------------------------------------------------
+// Run summary
+// ---------------------------------------------------------------------------
+
+/// The compared metrics, each as a sample over workers (or runs).
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct Metrics {
+    pub total_active_ms: Sample,
+    pub activations: Sample,
+    pub batched_in: Sample,
+}
+
+impl Metrics {
+    fn new(total_active_ms: &Stats, activations: &Stats, batched_in: &Stats, n: usize) -> Self {
+        Self {
+            total_active_ms: Sample::from_stats(total_active_ms, n),
+            activations: Sample::from_stats(activations, n),
+            batched_in: Sample::from_stats(batched_in, n),
+        }
+    }
+
+    /// Run stats already hold the unbiased variance.
+    fn over_runs(m: &RunMetrics, runs: usize) -> Self {
+        let sample = |s: &Stats| Sample {
+            mean: s.mean,
+            var: s.var,
+            n: runs,
+        };
+        Self {
+            total_active_ms: sample(&m.total_active_ms),
+            activations: sample(&m.activations),
+            batched_in: sample(&m.batched_in),
+        }
+    }
+}
+
+/// What `diff` needs from one snapshot.
+#[derive(Debug, Clone, Serialize)]
+pub struct RunSummary {
+    /// Snapshot label.
+    pub label: String,
+    pub has_time: bool,
+    pub has_memory: bool,
+    /// Mapped totals.
+    pub totals: Metrics,
+    /// Per block (stratum), by name.
+    pub blocks: BTreeMap<String, Metrics>,
+    /// Per rule, by `text (block)` with the block of its plan root, as the same text
+    /// can be planned in several strata. A text repeated within one block gets a
+    /// `#2`, `#3`, ... suffix in ops.json order.
+    pub rules: BTreeMap<String, Metrics>,
+    /// In node-name order.
+    pub nodes: Vec<NodeSummary>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeSummary {
+    pub name: String,
+    pub label: String,
+    pub block: String,
+    pub fingerprint: Option<String>,
+    pub metrics: Metrics,
+    /// In addr order.
+    pub operators: Vec<OperatorSummary>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OperatorSummary {
+    pub op_name: String,
+    pub addr: Vec<u32>,
+    pub metrics: Metrics,
+}
+
+impl RunSummary {
+    pub fn new(label: &str, data: &ReportData) -> Self {
+        let n = data.num_workers;
+        let runs = data.runs.as_ref().filter(|r| r.runs > 1);
+        let blocks = data
+            .blocks
+            .iter()
+            .map(|(name, b)| {
+                let metrics = runs
+                    .and_then(|r| Some(Metrics::over_runs(r.blocks.get(name)?, r.runs)))
+                    .unwrap_or_else(|| {
+                        Metrics::new(&b.total_active_ms, &b.activations, &b.batched_in, n)
+                    });
+                (name.clone(), metrics)
+            })
+            .collect();
+        let mut rules = BTreeMap::new();
+        for r in &data.rules {
+            let block = r
+                .nodes
+                .get(&r.root)
+                .and_then(|plan| data.nodes.get(plan.node.as_ref()?));
+            let name = match block {
+                Some(node) => format!("{} ({})", r.text, node.block),
+                None => r.text.clone(),
+            };
+            let (mut key, mut occurrence) = (name.clone(), 1);
+            while rules.contains_key(&key) {
+                occurrence += 1;
+                key = format!("{} #{}", name, occurrence);
+            }
+            let c = &r.cost;
+            rules.insert(
+                key,
+                Metrics::new(&c.total_active_ms, &c.activations, &c.batched_in, n),
+            );
+        }
+        let nodes = data
+            .nodes
+            .values()
+            .map(|v| NodeSummary {
+                name: v.name.clone(),
+                label: v.label.clone(),
+                block: v.block.clone(),
+                fingerprint: v.fingerprint.clone(),
+                metrics: runs
+                    .and_then(|r| Some(Metrics::over_runs(r.nodes.get(&v.name)?, r.runs)))
+                    .unwrap_or_else(|| {
+                        Metrics::new(
+                            &v.self_total_active_ms,
+                            &v.self_activations,
+                            &v.self_batched_in,
+                            n,
+                        )
+                    }),
+                operators: v
+                    .operators
+                    .iter()
+                    .map(|op| OperatorSummary {
+                        op_name: op.op_name.clone(),
+                        addr: op.addr.clone(),
+                        metrics: Metrics::new(
+                            &op.total_active_ms,
+                            &op.activations,
+                            op.batched_in.as_ref().unwrap_or(&Stats::default()),
+                            n,
+                        ),
+                    })
+                    .collect(),
+            })
+            .collect();
+        Self {
+            label: label.to_string(),
+            has_time: data.has_time,
+            has_memory: data.has_memory,
+            totals: runs
+                .map(|r| Metrics::over_runs(&r.totals, r.runs))
+                .unwrap_or_else(|| {
+                    Metrics::new(
+                        &data.totals.total_mapped_ms,
+                        &data.totals.total_mapped_activations,
+                        &data.totals.total_batched_in,
+                        n,
+                    )
+                }),
+            blocks,
+            rules,
+            nodes,
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Diff
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Delta {
+    pub base: f64,
+    pub new: f64,
+    /// `new - base`.
+    pub abs: f64,
+    /// `abs / base`; None when `base` is 0.
+    pub rel: Option<f64>,
+    /// Whether `abs` stands out from the worker (or run) spread; None when a side
+    /// has fewer than two workers.
+    pub significance: Option<Significance>,
+}
+
+impl Delta {
+    fn new(base: &Sample, new: &Sample, confidence: f64) -> Self {
+        let abs = new.mean - base.mean;
+        Self {
+            base: base.mean,
+            new: new.mean,
+            abs,
+            rel: (base.mean != 0.0).then(|| abs / base.mean),
+            significance: stats::welch_test(base, new, confidence),
+        }
+    }
+
+    /// Not shown to be noise: significant, or untestable.
+    pub fn is_significant(&self) -> bool {
+        self.significance.as_ref().is_none_or(|s| s.significant)
+    }
+}
+
+/// Deltas of the compared metrics; a metric is None unless both runs have its log.
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricDeltas {
+    pub total_active_ms: Option<Delta>,
+    pub activations: Option<Delta>,
+    pub batched_in: Option<Delta>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchedBy {
+    /// Same fingerprint in the same block.
+    Fingerprint,
+    LabelBlock,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeDiff {
+    pub base_name: String,
+    pub new_name: String,
+    pub label: String,
+    pub block: String,
+    pub matched_by: MatchedBy,
+    pub deltas: MetricDeltas,
+    pub operators: Vec<OperatorDiff>,
+    /// Operators of the node found in only one run (op_name has no counterpart).
+    pub added_operators: Vec<OperatorRef>,
+    pub removed_operators: Vec<OperatorRef>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OperatorDiff {
+    pub op_name: String,
+    pub base_addr: Vec<u32>,
+    pub new_addr: Vec<u32>,
+    pub deltas: MetricDeltas,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OperatorRef {
+    pub op_name: String,
+    pub addr: Vec<u32>,
+    pub total_active_ms: f64,
+}
+
+/// A node present in only one run.
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeRef {
+    pub name: String,
+    pub label: String,
+    pub block: String,
+    pub fingerprint: Option<String>,
+    pub total_active_ms: Option<f64>,
+    pub batched_in: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NamedDiff {
+    pub name: String,
+    pub deltas: MetricDeltas,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiffReport {
+    pub base_label: String,
+    pub new_label: String,
+    /// Confidence level of the significance tests (e.g. 0.95).
+    pub confidence: f64,
+    pub totals: MetricDeltas,
+    /// Per block (stratum), by name.
+    pub blocks: Vec<NamedDiff>,
+    /// Per rule, by `text (block)` (see `RunSummary::rules`).
+    pub rules: Vec<NamedDiff>,
+    pub nodes: Vec<NodeDiff>,
+    pub added_nodes: Vec<NodeRef>,
+    pub removed_nodes: Vec<NodeRef>,
+    pub added_rules: Vec<String>,
+    pub removed_rules: Vec<String>,
+}
+
+/// Compare run `new` against run `base`, testing deltas at `confidence`.
+pub fn diff_reports(base: &RunSummary, new: &RunSummary, confidence: f64) -> DiffReport {
+    let time = base.has_time && new.has_time;
+    let memory = base.has_memory && new.has_memory;
+    let deltas = |b: &Metrics, n: &Metrics| MetricDeltas {
+        total_active_ms: time
+            .then(|| Delta::new(&b.total_active_ms, &n.total_active_ms, confidence)),
+        activations: time.then(|| Delta::new(&b.activations, &n.activations, confidence)),
+        batched_in: memory.then(|| Delta::new(&b.batched_in, &n.batched_in, confidence)),
+    };
+
+    let totals = deltas(&base.totals, &new.totals);
+
+    let blocks: BTreeSet<&String> = base.blocks.keys().chain(new.blocks.keys()).collect();
+    let blocks = blocks
+        .into_iter()
+        .map(|block| {
+            let get = |run: &RunSummary| run.blocks.get(block).copied().unwrap_or_default();
+            NamedDiff {
+                name: block.clone(),
+                deltas: deltas(&get(base), &get(new)),
+            }
+        })
+        .collect();
+
+    let rules = base
+        .rules
+        .iter()
+        .filter_map(|(text, b)| {
+            let n = new.rules.get(text)?;
+            Some(NamedDiff {
+                name: text.clone(),
+                deltas: deltas(b, n),
+            })
+        })
+        .collect();
+    let added_rules = new
+        .rules
+        .keys()
+        .filter(|t| !base.rules.contains_key(*t))
+        .cloned()
+        .collect();
+    let removed_rules = base
+        .rules
+        .keys()
+        .filter(|t| !new.rules.contains_key(*t))
+        .cloned()
+        .collect();
+
+    let (pairs, added, removed) = match_nodes(base, new);
+    let nodes = pairs
+        .into_iter()
+        .map(|(b, n, matched_by)| {
+            let (operators, added_operators, removed_operators) =
+                match_operators(&b.operators, &n.operators);
+            NodeDiff {
+                base_name: b.name.clone(),
+                new_name: n.name.clone(),
+                label: n.label.clone(),
+                block: n.block.clone(),
+                matched_by,
+                deltas: deltas(&b.metrics, &n.metrics),
+                operators: operators
+                    .into_iter()
+                    .map(|(bo, no)| OperatorDiff {
+                        op_name: no.op_name.clone(),
+                        base_addr: bo.addr.clone(),
+                        new_addr: no.addr.clone(),
+                        deltas: deltas(&bo.metrics, &no.metrics),
+                    })
+                    .collect(),
+                added_operators,
+                removed_operators,
+            }
+        })
+        .collect();
+
+    let node_ref = |v: &NodeSummary, run: &RunSummary| NodeRef {
+        name: v.name.clone(),
+        label: v.label.clone(),
+        block: v.block.clone(),
+        fingerprint: v.fingerprint.clone(),
+        total_active_ms: run.has_time.then_some(v.metrics.total_active_ms.mean),
+        batched_in: run.has_memory.then_some(v.metrics.batched_in.mean),
+    };
+
+    DiffReport {
+        base_label: base.label.clone(),
+        new_label: new.label.clone(),
+        confidence,
+        totals,
+        blocks,
+        rules,
+        nodes,
+        added_nodes: added.into_iter().map(|v| node_ref(v, new)).collect(),
+        removed_nodes: removed.into_iter().map(|v| node_ref(v, base)).collect(),
+        added_rules,
+        removed_rules,
+    }
+}
+
+type NodePairs<'a> = Vec<(&'a NodeSummary, &'a NodeSummary, MatchedBy)>;
+
+/// Pair nodes by (fingerprint, block), as fingerprints are only unique per block,
+/// then the rest by (label, block). Nodes sharing a key are paired in node id order;
+/// the leftovers are added (new only) or removed (base only).
+fn match_nodes<'a>(
+    base: &'a RunSummary,
+    new: &'a RunSummary,
+) -> (NodePairs<'a>, Vec<&'a NodeSummary>, Vec<&'a NodeSummary>) {
+    let mut pairs = Vec::new();
+    let mut base_left: Vec<&NodeSummary> = base.nodes.iter().collect();
+    let mut new_left: Vec<&NodeSummary> = new.nodes.iter().collect();
+    // Names are ops.json ids: "10" comes after "2".
+    let id_order = |v: &&NodeSummary| (v.name.parse::<u64>().unwrap_or(u64::MAX), v.name.clone());
+    base_left.sort_by_key(id_order);
+    new_left.sort_by_key(id_order);
+    pair_by(
+        &mut base_left,
+        &mut new_left,
+        &mut pairs,
+        MatchedBy::Fingerprint,
+        |v| {
+            v.fingerprint
+                .as_ref()
+                .map(|fp| format!("{}\u{0}{}", fp, v.block))
+        },
+    );
+    pair_by(
+        &mut base_left,
+        &mut new_left,
+        &mut pairs,
+        MatchedBy::LabelBlock,
+        |v| Some(format!("{}\u{0}{}", v.label, v.block)),
+    );
+    (pairs, new_left, base_left)
+}
+
+/// Move the nodes of `base_left` and `new_left` with equal keys into `pairs`.
+fn pair_by<'a>(
+    base_left: &mut Vec<&'a NodeSummary>,
+    new_left: &mut Vec<&'a NodeSummary>,
+    pairs: &mut NodePairs<'a>,
+    matched_by: MatchedBy,
+    key: impl Fn(&NodeSummary) -> Option<String>,
+) {
+    let mut by_key: BTreeMap<String, Vec<&'a NodeSummary>> = BTreeMap::new();
+    for v in base_left.iter() {
+        if let Some(k) = key(v) {
+            by_key.entry(k).or_default().push(v);
+        }
+    }
+    let mut matched: BTreeSet<&str> = BTreeSet::new();
+    new_left.retain(|n| {
+        let Some(candidates) = key(n)
+            .and_then(|k| by_key.get_mut(&k))
+            .filter(|c| !c.is_empty())
+        else {
+            return true;
+        };
+        let b = candidates.remove(0);
+        matched.insert(&b.name);
+        pairs.push((b, *n, matched_by));
+        false
+    });
+    base_left.retain(|b| !matched.contains(b.name.as_str()));
+}
+
+type OperatorPairs<'a> = Vec<(&'a OperatorSummary, &'a OperatorSummary)>;
+
+/// Pair operators by op_name, in addr order for repeated names.
+fn match_operators<'a>(
+    base: &'a [OperatorSummary],
+    new: &'a [OperatorSummary],
+) -> (OperatorPairs<'a>, Vec<OperatorRef>, Vec<OperatorRef>) {
+    let mut by_name: BTreeMap<&str, Vec<&OperatorSummary>> = BTreeMap::new();
+    for op in base {
+        by_name.entry(&op.op_name).or_default().push(op);
+    }
+    let op_ref = |op: &OperatorSummary| OperatorRef {
+        op_name: op.op_name.clone(),
+        addr: op.addr.clone(),
+        total_active_ms: op.metrics.total_active_ms.mean,
+    };
+
+    let mut pairs = Vec::new();
+    let mut added = Vec::new();
+    for op in new {
+        match by_name
+            .get_mut(op.op_name.as_str())
+            .filter(|c| !c.is_empty())
+        {
+            Some(candidates) => pairs.push((candidates.remove(0), op)),
+            None => added.push(op_ref(op)),
+        }
+    }
+    let removed = by_name.into_values().flatten().map(op_ref).collect();
+    (pairs, added, removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::view::SharedPolicy;
+    use crate::view::tests::{program, report, time};
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    fn node(name: &str, label: &str, block: &str, fingerprint: Option<&str>) -> NodeSummary {
+        NodeSummary {
+            name: name.to_string(),
+            label: label.to_string(),
+            block: block.to_string(),
+            fingerprint: fingerprint.map(String::from),
+            metrics: Metrics::default(),
+            operators: Vec::new(),
+        }
+    }
+
+    fn run(nodes: Vec<NodeSummary>) -> RunSummary {
+        RunSummary {
+            label: "t0".to_string(),
+            has_time: true,
+            has_memory: false,
+            totals: Metrics::default(),
+            blocks: BTreeMap::new(),
+            rules: BTreeMap::new(),
+            nodes,
+        }
+    }
+
+    /// (base name, new name) of each pair, in base name order.
+    fn paired(base: &RunSummary, new: &RunSummary) -> Vec<(String, String)> {
+        let (pairs, _, _) = match_nodes(base, new);
+        let mut names: Vec<_> = pairs
+            .iter()
+            .map(|(b, n, _)| (b.name.clone(), n.name.clone()))
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn matches_a_fingerprint_within_its_block() {
+        // The same fingerprint in two strata, with the node ids in the other order.
+        let base = run(vec![
+            node("10", "Join", "stratum 9", Some("0xa")),
+            node("2", "Join", "stratum 1", Some("0xa")),
+        ]);
+        let new = run(vec![
+            node("20", "Join", "stratum 1", Some("0xa")),
+            node("3", "Join", "stratum 9", Some("0xa")),
+        ]);
+        assert_eq!(
+            paired(&base, &new),
+            vec![
+                ("10".to_string(), "3".to_string()),
+                ("2".to_string(), "20".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn pairs_nodes_sharing_a_key_in_id_order() {
+        // Without fingerprints, (label, block) ties pair by id: 9 -> 10, 10 -> 11.
+        let base = run(vec![
+            node("10", "Map", "stratum 1", None),
+            node("9", "Map", "stratum 1", None),
+        ]);
+        let new = run(vec![
+            node("10", "Map", "stratum 1", None),
+            node("11", "Map", "stratum 1", None),
+        ]);
+        assert_eq!(
+            paired(&base, &new),
+            vec![
+                ("10".to_string(), "11".to_string()),
+                ("9".to_string(), "10".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn leftovers_are_added_or_removed() {
+        let base = run(vec![node("1", "Map", "stratum 1", Some("0xa"))]);
+        let new = run(vec![
+            node("1", "Map", "stratum 2", Some("0xa")),
+            node("2", "Filter", "stratum 2", None),
+        ]);
+        let (pairs, added, removed) = match_nodes(&base, &new);
+        assert!(pairs.is_empty());
+        let names = |nodes: Vec<&NodeSummary>| -> Vec<String> {
+            nodes.into_iter().map(|n| n.name.clone()).collect()
+        };
+        assert_eq!(names(added), vec!["1", "2"]);
+        assert_eq!(names(removed), vec!["1"]);
+    }
+
+    #[test]
+    fn rules_with_the_same_text_keep_their_own_costs() {
+        // One rule text, planned in stratum 1 and twice in stratum 0.
+        let program = program(json!({
+            "nodes": [
+                { "id": 0, "block": "stratum 0", "fingerprint": "x", "operators": [[0, 1]] },
+                { "id": 1, "block": "stratum 1", "fingerprint": "y", "operators": [[0, 2]] },
+                { "id": 2, "block": "stratum 0", "fingerprint": "z", "operators": [[0, 3]] },
+            ],
+            "rules": [
+                { "text": "p(x) :- e(x).", "plan_tree": [{ "fingerprint": "x" }] },
+                { "text": "p(x) :- e(x).", "plan_tree": [{ "fingerprint": "y" }] },
+                { "text": "p(x) :- e(x).", "plan_tree": [{ "fingerprint": "z" }] },
+            ],
+        }));
+        let summary = |ms: [f64; 3]| {
+            let time = time(&[
+                (&[0, 1], "Map", [ms[0]; 2]),
+                (&[0, 2], "Map", [ms[1]; 2]),
+                (&[0, 3], "Map", [ms[2]; 2]),
+            ]);
+            RunSummary::new("t0", &report(&program, &time, SharedPolicy::Split))
+        };
+        let diff = diff_reports(&summary([1.0, 2.0, 4.0]), &summary([1.0, 5.0, 3.0]), 0.95);
+
+        let rules: Vec<(&str, f64)> = diff
+            .rules
+            .iter()
+            .map(|r| {
+                (
+                    r.name.as_str(),
+                    r.deltas.total_active_ms.as_ref().unwrap().abs,
+                )
+            })
+            .collect();
+        assert_eq!(
+            rules,
+            vec![
+                ("p(x) :- e(x). (stratum 0)", 0.0),
+                ("p(x) :- e(x). (stratum 0) #2", -1.0),
+                ("p(x) :- e(x). (stratum 1)", 3.0),
+            ]
+        );
+        assert!(diff.added_rules.is_empty() && diff.removed_rules.is_empty());
+    }
+}
@@ -0,0 +1,154 @@
+//! JSON export of the analysis model, for tools other than the HTML report
+//! (notebooks, dashboards, `diff` and `check`).
+//!
+//! The export is the full `ReportData` of every snapshot, as the report builds it,
+//! wrapped with a schema version. Maps are sorted by key and snapshots keep their
+//! label order, so two exports of the same run are byte-identical and exports of two
+//! runs diff cleanly.
+//!
+//! Versioning: `SCHEMA_VERSION` is bumped when a field is removed, renamed or changes
+//! meaning. Adding a field does not bump it; readers must ignore unknown fields.
+
+use crate::Result;
+use crate::diagnostics::{self, Diagnostic};
+use crate::view::ReportData;
+
+use anyhow::{Context, bail};
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Export {
+    pub schema_version: u32,
+    /// `flowlog-profile-viz <version>` that wrote the file; informational only.
+    pub generator: String,
+    pub snapshot_labels: Vec<String>,
+    /// Cumulative snapshots, parallel to `snapshot_labels`.
+    pub snapshots: Vec<ReportData>,
+    /// Increments since the previous snapshot, parallel to `snapshot_labels`; empty
+    /// with a single snapshot.
+    pub interval_snapshots: Vec<ReportData>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Export {
+    pub fn new(
+        labels: &[String],
+        snapshots: &[ReportData],
+        interval_snapshots: &[ReportData],
+        diagnostics: &[Diagnostic],
+    ) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            generator: format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+            snapshot_labels: labels.to_vec(),
+            snapshots: snapshots.to_vec(),
+            interval_snapshots: interval_snapshots.to_vec(),
+            diagnostics: diagnostics.to_vec(),
+        }
+    }
+}
+
+/// Just the version, read first so that an export of another version fails with a
+/// clear message instead of a missing-field error.
+#[derive(Deserialize)]
+struct SchemaVersion {
+    schema_version: u32,
+}
+
+pub fn render_json(export: &Export) -> Result<String> {
+    Ok(serde_json::to_string_pretty(export)?)
+}
+
+/// Load an export written by `--format json`, rejecting other schema versions.
+pub fn read_export(path: &str) -> Result<Export> {
+    let text = fs::read_to_string(path)
+        .with_context(|| diagnostics::error_message(format!("read export file {}", path)))?;
+    let version: SchemaVersion = serde_json::from_str(&text)
+        .with_context(|| diagnostics::error_message(format!("parse export file {}", path)))?;
+    if version.schema_version != SCHEMA_VERSION {
+        bail!(
+            "{}",
+            diagnostics::error_message(format!(
+                "export file {} has schema version {}, this build reads version {}",
+                path, version.schema_version, SCHEMA_VERSION
+            ))
+        );
+    }
+    serde_json::from_str(&text)
+        .with_context(|| diagnostics::error_message(format!("parse export file {}", path)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::addr::Addr;
+    use crate::diagnostics::Diagnostics;
+    use crate::log::{Snapshot, TimeRow};
+    use crate::ops::OpsSpec;
+    use crate::stats::Stats;
+    use crate::view::{self, Program, SharedPolicy};
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn an_export_reads_back_unchanged() {
+        // Node 1 logged zero time, and node 2 has no row in the log at all.
+        let mut diags = Diagnostics::discard();
+        let spec: OpsSpec = serde_json::from_value(json!({
+            "nodes": [
+                { "id": 0, "block": "input", "tags": ["Input"], "operators": [[0, 1]] },
+                { "id": 1, "block": "stratum 0", "operators": [[0, 2]], "parents": [0] },
+                { "id": 2, "block": "stratum 0", "operators": [[0, 3]], "parents": [1] },
+            ],
+        }))
+        .unwrap();
+        let program = Program::new(
+            spec.validate_and_build("ops.json", &mut diags).unwrap(),
+            &mut diags,
+        );
+        let row = |ms: [f64; 2]| TimeRow {
+            activations: Stats::from_values(&[1.0, 1.0]),
+            total_active_ms: Stats::from_values(&ms),
+            activations_by_worker: vec![1.0, 1.0],
+            total_active_ms_by_worker: ms.to_vec(),
+            extra: BTreeMap::new(),
+            op_name: "Map".to_string(),
+            num_workers: 2,
+        };
+        let time = Snapshot {
+            label: "t0".to_string(),
+            workers: vec![0, 1],
+            data: BTreeMap::from([
+                (Addr::new(vec![0, 1]), row([1.0, 2.0])),
+                (Addr::new(vec![0, 2]), row([0.0, 0.0])),
+            ]),
+        };
+        let data =
+            view::build_report_data(&program, Some(&time), None, SharedPolicy::Split, &mut diags)
+                .unwrap();
+        assert_eq!(data.nodes["1"].self_total_active_ms.max, 0.0);
+
+        let written = render_json(&Export::new(
+            &["t0".to_string()],
+            &[data],
+            &[],
+            diags.items(),
+        ))
+        .unwrap();
+        let path = std::env::temp_dir().join(format!(
+            "flowlog-profile-viz-export-{}.json",
+            std::process::id()
+        ));
+        fs::write(&path, &written).unwrap();
+        let read = read_export(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+
+        let read = read.unwrap();
+        assert_eq!(read.snapshot_labels, vec!["t0"]);
+        assert_eq!(render_json(&read).unwrap(), written);
+    }
+}
@@ -0,0 +1,174 @@
+//! Folded stacks (`frame;frame;... weight` lines) for flame graph tools such as
+//! inferno, flamegraph.pl and speedscope.
+//!
+//! Every mapped operator is one stack: its node's block, the node's primary-tree
+//! ancestors within that block, the node, then the operator (`op_name` and the last
+//! address component). A frame's width is then its own operators plus everything
+//! below it in the tree, and a block's width is the block total. Weights are
+//! integers, as the tools expect: time in microseconds and batched_in in records.
+
+use crate::Result;
+use crate::diagnostics;
+use crate::view::{OperatorView, ReportData};
+
+use anyhow::Context;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Write the folded stacks of one snapshot into `dir` (created if missing):
+/// `time.folded` (mean over workers) and `time_by_worker.folded` (one `worker N`
+/// root frame per worker) with a time log, `batched_in.folded` (mean over workers)
+/// with a memory log.
+pub fn write_folded(dir: &str, data: &ReportData) -> Result<()> {
+    fs::create_dir_all(dir)
+        .with_context(|| diagnostics::error_message(format!("create output directory {}", dir)))?;
+    let stacks = operator_stacks(data);
+
+    if data.has_time {
+        let mut mean = BTreeMap::new();
+        let mut by_worker = BTreeMap::new();
+        for (stack, op) in &stacks {
+            add(&mut mean, stack.clone(), op.total_active_ms.mean * 1000.0);
+            for (worker, ms) in data.workers.iter().zip(&op.workers.total_active_ms) {
+                add(
+                    &mut by_worker,
+                    format!("worker {};{}", worker, stack),
+                    ms * 1000.0,
+                );
+            }
+        }
+        write_stacks(&Path::new(dir).join("time.folded"), mean)?;
+        write_stacks(&Path::new(dir).join("time_by_worker.folded"), by_worker)?;
+    }
+    if data.has_memory {
+        let mut batched_in = BTreeMap::new();
+        for (stack, op) in &stacks {
+            if let Some(b) = &op.batched_in {
+                add(&mut batched_in, stack.clone(), b.mean);
+            }
+        }
+        write_stacks(&Path::new(dir).join("batched_in.folded"), batched_in)?;
+    }
+    Ok(())
+}
+
+/// Each mapped operator with its stack.
+fn operator_stacks(data: &ReportData) -> Vec<(String, &OperatorView)> {
+    let mut parents: BTreeMap<&str, &str> = BTreeMap::new();
+    for (name, node) in &data.nodes {
+        for child in &node.children {
+            parents.insert(child, name);
+        }
+    }
+
+    let mut stacks = Vec::new();
+    for (name, node) in &data.nodes {
+        // The node and its ancestors up to the first one in another block.
+        let mut frames = vec![frame(&node.label)];
+        let mut current = name.as_str();
+        while let Some(parent) = parents.get(current).and_then(|p| data.nodes.get(*p)) {
+            if parent.block != node.block {
+                break;
+            }
+            frames.push(frame(&parent.label));
+            current = &parent.name;
+        }
+        frames.push(frame(&node.block));
+        frames.reverse();
+        let prefix = frames.join(";");
+
+        for op in &node.operators {
+            let id = op
+                .addr
+                .last()
+                .map_or_else(String::new, |id| format!(" {}", id));
+            stacks.push((format!("{};{}{}", prefix, frame(&op.op_name), id), op));
+        }
+    }
+    stacks
+}
+
+/// A label as one frame: `;` separates frames and a line is one stack.
+fn frame(label: &str) -> String {
+    label.replace(';', ",").replace(['\n', '\r'], " ")
+}
+
+/// Add a weight to a stack; distinct nodes with the same label and ancestors share
+/// a stack, as functions of the same name do in a CPU profile.
+fn add(stacks: &mut BTreeMap<String, f64>, stack: String, weight: f64) {
+    *stacks.entry(stack).or_default() += weight;
+}
+
+/// One `stack weight` line per stack with a non-zero rounded weight.
+fn write_stacks(path: &Path, stacks: BTreeMap<String, f64>) -> Result<()> {
+    let lines: Vec<String> = stacks
+        .into_iter()
+        .map(|(stack, weight)| (stack, weight.round() as u64))
+        .filter(|(_, weight)| *weight > 0)
+        .map(|(stack, weight)| format!("{} {}\n", stack, weight))
+        .collect();
+    fs::write(path, lines.concat()).with_context(|| {
+        diagnostics::error_message(format!("write folded stacks {}", path.display()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::view::SharedPolicy;
+    use crate::view::tests::{program, report, time};
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn stacks_run_from_the_block_through_same_block_ancestors() {
+        let program = program(json!({
+            "nodes": [
+                { "id": 0, "name": "scan", "block": "input", "operators": [[0, 1]] },
+                {
+                    "id": 1, "name": "join;x", "block": "stratum 0",
+                    "operators": [[0, 2]], "parents": [0],
+                },
+                {
+                    "id": 2, "name": "map", "block": "stratum 0",
+                    "operators": [[0, 3]], "parents": [1],
+                },
+            ],
+        }));
+        let time = time(&[
+            (&[0, 1], "Input", [1.0, 3.0]),
+            (&[0, 2], "Join", [2.0, 2.0]),
+            (&[0, 3], "Map", [0.5, 0.5]),
+        ]);
+        let data = report(&program, &time, SharedPolicy::Split);
+
+        let dir =
+            std::env::temp_dir().join(format!("flowlog-profile-viz-folded-{}", std::process::id()));
+        let dir = dir.to_str().unwrap();
+        write_folded(dir, &data).unwrap();
+        let read = |file: &str| fs::read_to_string(Path::new(dir).join(file));
+        let (mean, by_worker) = (read("time.folded"), read("time_by_worker.folded"));
+        let batched_in = Path::new(dir).join("batched_in.folded").exists();
+        fs::remove_dir_all(dir).unwrap();
+
+        // `;` in a label would start a new frame. Weights are in µs.
+        assert_eq!(
+            mean.unwrap(),
+            "input;scan;Input 1 2000\n\
+             stratum 0;join,x;Join 2 2000\n\
+             stratum 0;join,x;map;Map 3 500\n"
+        );
+        assert_eq!(
+            by_worker.unwrap(),
+            "worker 0;input;scan;Input 1 1000\n\
+             worker 0;stratum 0;join,x;Join 2 2000\n\
+             worker 0;stratum 0;join,x;map;Map 3 500\n\
+             worker 1;input;scan;Input 1 3000\n\
+             worker 1;stratum 0;join,x;Join 2 2000\n\
+             worker 1;stratum 0;join,x;map;Map 3 500\n"
+        );
+        // Without a memory log there is no batched_in file.
+        assert!(!batched_in);
+    }
+}
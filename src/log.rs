@@ -2,10 +2,13 @@
 //!
 //! Supports reading a folder of per-worker log files (e.g. time_worker_0.log,
 //! time_worker_1.log, ...) and aggregating into mean + variance across workers.
+//!
+//! Columns are mapped by name from each file's header row (`addr <metric>... name`),
+//! so new numeric columns pass through as extra metrics instead of breaking parsing.
 
 use crate::Result;
 use crate::addr::Addr;
-use crate::diagnostics;
+use crate::diagnostics::{self, Diagnostics, Location};
 use crate::stats::Stats;
 
 use anyhow::{Context, anyhow, bail};
@@ -24,6 +27,11 @@ use std::sync::LazyLock;
 pub struct TimeRow {
     pub activations: Stats,
     pub total_active_ms: Stats,
+    /// Per-worker values, indexed like `Snapshot::workers` (0 where the worker has no row).
+    pub activations_by_worker: Vec<f64>,
+    pub total_active_ms_by_worker: Vec<f64>,
+    /// Numeric columns beyond the known ones, keyed by header name.
+    pub extra: BTreeMap<String, Stats>,
     pub op_name: String,
     pub num_workers: usize,
 }
@@ -38,36 +46,38 @@ pub struct MemoryRow {
     pub merge_in: Stats,
     pub merge_out: Stats,
     pub dropped: Stats,
+    /// Per-worker values, indexed like `Snapshot::workers` (0 where the worker has no row).
+    pub batched_in_by_worker: Vec<f64>,
+    pub merges_by_worker: Vec<f64>,
+    pub merge_in_by_worker: Vec<f64>,
+    pub merge_out_by_worker: Vec<f64>,
+    pub dropped_by_worker: Vec<f64>,
+    /// Numeric columns beyond the known ones, keyed by header name.
+    pub extra: BTreeMap<String, Stats>,
     pub op_name: String,
     pub num_workers: usize,
 }
 
 pub type MemoryIndex = BTreeMap<Addr, MemoryRow>;
 
+/// Known columns of the time log, in the order used when a file has no header.
+const TIME_COLUMNS: &[&str] = &["activations", "total_active_ms"];
+
+/// Known columns of the memory log, in the order used when a file has no header.
+const MEMORY_COLUMNS: &[&str] = &["batched_in", "merges", "merge_in", "merge_out", "dropped"];
+
 // ---------------------------------------------------------------------------
 // Raw per-worker row types (internal)
 // ---------------------------------------------------------------------------
 
+/// One row of one worker's log: every numeric column by header name.
 #[derive(Debug, Clone)]
-struct RawTimeRow {
-    activations: u64,
-    total_active_ms: f64,
+struct RawRow {
+    values: BTreeMap<String, f64>,
     op_name: String,
 }
 
-type RawTimeIndex = BTreeMap<Addr, RawTimeRow>;
-
-#[derive(Debug, Clone)]
-struct RawMemoryRow {
-    batched_in: u64,
-    merges: u64,
-    merge_in: u64,
-    merge_out: u64,
-    dropped: u64,
-    op_name: String,
-}
-
-type RawMemoryIndex = BTreeMap<Addr, RawMemoryRow>;
+type RawIndex = BTreeMap<Addr, RawRow>;
 
 // ---------------------------------------------------------------------------
 // Public API: folder-based parsing (returns snapshots grouped by timestamp)
@@ -76,24 +86,30 @@ type RawMemoryIndex = BTreeMap<Addr, RawMemoryRow>;
 /// A labeled snapshot of aggregated data (one per timestamp, or one "batch" for non-timestamped).
 pub struct Snapshot<T> {
     pub label: String,
+    /// Worker index of each file in the snapshot, parsed from the filename (ascending).
+    pub workers: Vec<u32>,
     pub data: T,
 }
 
 /// Parse all time log files in a folder, grouped by timestamp.
-pub fn parse_time_folder(dir: &str) -> Result<Vec<Snapshot<TimeIndex>>> {
-    parse_folder(dir, "time", parse_raw_time_file, aggregate_time)
+pub fn parse_time_folder(dir: &str, diags: &mut Diagnostics) -> Result<Vec<Snapshot<TimeIndex>>> {
+    parse_folder(dir, "time", TIME_COLUMNS, aggregate_time, diags)
 }
 
 /// Parse all memory log files in a folder, grouped by timestamp.
-pub fn parse_memory_folder(dir: &str) -> Result<Vec<Snapshot<MemoryIndex>>> {
-    parse_folder(dir, "memory", parse_raw_memory_file, aggregate_memory)
+pub fn parse_memory_folder(
+    dir: &str,
+    diags: &mut Diagnostics,
+) -> Result<Vec<Snapshot<MemoryIndex>>> {
+    parse_folder(dir, "memory", MEMORY_COLUMNS, aggregate_memory, diags)
 }
 
-fn parse_folder<R, T>(
+fn parse_folder<T>(
     dir: &str,
     kind: &str,
-    parse_file: fn(&str) -> Result<R>,
-    aggregate: fn(&[R], &[String]) -> Result<T>,
+    known_columns: &[&str],
+    aggregate: fn(&[RawIndex], &[String], &mut Diagnostics) -> Result<T>,
+    diags: &mut Diagnostics,
 ) -> Result<Vec<Snapshot<T>>> {
     let files = collect_log_files(dir)?;
     if files.is_empty() {
@@ -103,21 +119,157 @@ fn parse_folder<R, T>(
         );
     }
 
-    let groups = group_by_timestamp(&files);
+    let groups = group_by_timestamp(&files, diags)?;
+    check_worker_completeness(kind, &groups, diags);
+
     let mut snapshots = Vec::new();
-    for (label, group_files) in groups {
+    for (label, group) in groups {
+        let (worker_ids, group_files): (Vec<u32>, Vec<String>) = group.into_iter().unzip();
         let mut workers = Vec::new();
         for f in &group_files {
-            workers.push(parse_file(f)?);
+            workers.push(parse_raw_file(f, kind, known_columns, diags)?);
         }
         snapshots.push(Snapshot {
             label,
-            data: aggregate(&workers, &group_files)?,
+            workers: worker_ids,
+            data: aggregate(&workers, &group_files, diags)?,
         });
     }
     Ok(snapshots)
 }
 
+/// Warn about worker gaps within a snapshot and worker sets that change between snapshots.
+fn check_worker_completeness(
+    kind: &str,
+    groups: &[(String, WorkerFiles)],
+    diags: &mut Diagnostics,
+) {
+    let mut first: Option<(&str, Vec<u32>)> = None;
+    for (label, group) in groups {
+        let ids: Vec<u32> = group.iter().map(|(w, _)| *w).collect();
+        let max = ids.last().copied().unwrap_or(0);
+        let missing: Vec<u32> = (0..=max)
+            .filter(|w| ids.binary_search(w).is_err())
+            .collect();
+        if !missing.is_empty() {
+            diags.warning(
+                None,
+                format!(
+                    "{} snapshot {} is missing worker(s) {:?} (found {} of workers 0..={})",
+                    kind,
+                    label,
+                    missing,
+                    ids.len(),
+                    max
+                ),
+            );
+        }
+
+        match &first {
+            None => first = Some((label, ids)),
+            Some((first_label, first_ids)) => {
+                if *first_ids != ids {
+                    diags.warning(
+                        None,
+                        format!(
+                            "{} snapshot {} covers {} but snapshot {} covers {}",
+                            kind,
+                            label,
+                            describe_worker_diff(&ids, first_ids),
+                            first_label,
+                            describe_worker_diff(first_ids, &ids)
+                        ),
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Time and memory snapshot with the same label; either side may be missing.
+pub type SnapshotPair<'a> = (
+    String,
+    Option<&'a Snapshot<TimeIndex>>,
+    Option<&'a Snapshot<MemoryIndex>>,
+);
+
+/// Pair time and memory snapshots by label, in timestamp order.
+pub fn pair_snapshots<'a>(
+    time: &'a [Snapshot<TimeIndex>],
+    memory: &'a [Snapshot<MemoryIndex>],
+) -> Vec<SnapshotPair<'a>> {
+    // Labels are `t<N>` (see `group_by_timestamp`), so order them by N.
+    let order = |label: &str| label.trim_start_matches('t').parse::<u64>().unwrap_or(0);
+
+    let mut pairs: BTreeMap<u64, SnapshotPair<'a>> = BTreeMap::new();
+    for s in time {
+        pairs
+            .entry(order(&s.label))
+            .or_insert((s.label.clone(), None, None))
+            .1 = Some(s);
+    }
+    for s in memory {
+        pairs
+            .entry(order(&s.label))
+            .or_insert((s.label.clone(), None, None))
+            .2 = Some(s);
+    }
+
+    pairs.into_values().collect()
+}
+
+/// When both folders were given, warn about snapshots found in only one of them.
+pub fn check_snapshot_pairs(pairs: &[SnapshotPair<'_>], diags: &mut Diagnostics) {
+    let both =
+        pairs.iter().any(|(_, ts, _)| ts.is_some()) && pairs.iter().any(|(_, _, ms)| ms.is_some());
+    for (label, ts, ms) in pairs {
+        if both && (ts.is_none() || ms.is_none()) {
+            let (present, absent) = if ts.is_some() {
+                ("time", "memory")
+            } else {
+                ("memory", "time")
+            };
+            diags.warning(
+                None,
+                format!(
+                    "snapshot {} is only in the {} folder; its {} data is missing",
+                    label, present, absent
+                ),
+            );
+        }
+    }
+}
+
+/// Warn when the time and memory logs of one snapshot cover different workers.
+pub fn check_worker_sets_match(
+    label: &str,
+    time_workers: &[u32],
+    memory_workers: &[u32],
+    diags: &mut Diagnostics,
+) {
+    if time_workers != memory_workers {
+        diags.warning(
+            None,
+            format!(
+                "snapshot {}: time log covers {} while memory log covers {}",
+                label,
+                describe_worker_diff(time_workers, memory_workers),
+                describe_worker_diff(memory_workers, time_workers)
+            ),
+        );
+    }
+}
+
+/// Describe `ids` relative to `other`: its size plus the ids that `other` lacks.
+fn describe_worker_diff(ids: &[u32], other: &[u32]) -> String {
+    let only: Vec<u32> = ids.iter().filter(|w| !other.contains(w)).copied().collect();
+    if only.is_empty() {
+        format!("{} worker(s)", ids.len())
+    } else {
+        format!("{} worker(s) ({:?} only there)", ids.len(), only)
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Aggregation helpers
 // ---------------------------------------------------------------------------
@@ -129,16 +281,18 @@ fn validate_op_name(
     addr: &Addr,
     first_file: &str,
     current_file: &str,
+    diags: &mut Diagnostics,
 ) -> Result<()> {
+    // In lenient mode a mismatch keeps the first op_name seen.
     if let Some(existing) = op_name.as_ref() {
         if existing != candidate {
-            bail!(
-                "{}",
-                diagnostics::error_message(format!(
+            diags.error(
+                Some(Location::file(current_file)),
+                format!(
                     "op_name mismatch for addr {:?} between {} ({:?}) and {} ({:?})",
                     addr.0, first_file, existing, current_file, candidate
-                ))
-            );
+                ),
+            )?;
         }
     } else {
         *op_name = Some(candidate.to_string());
@@ -146,36 +300,74 @@ fn validate_op_name(
     Ok(())
 }
 
-fn aggregate_time(workers: &[RawTimeIndex], files: &[String]) -> Result<TimeIndex> {
+/// Per-worker values of every column for one addr (0 where a worker has no row),
+/// plus the op_name all workers agree on.
+fn worker_values(
+    workers: &[RawIndex],
+    addr: &Addr,
+    files: &[String],
+    diags: &mut Diagnostics,
+) -> Result<(BTreeMap<String, Vec<f64>>, String)> {
+    let n = workers.len();
+    let mut values: BTreeMap<String, Vec<f64>> = BTreeMap::new();
+    let mut op_name: Option<String> = None;
+
+    for (wi, w) in workers.iter().enumerate() {
+        let Some(row) = w.get(addr) else {
+            continue;
+        };
+        validate_op_name(
+            &mut op_name,
+            &row.op_name,
+            addr,
+            &files[0],
+            &files[wi],
+            diags,
+        )?;
+        for (column, v) in &row.values {
+            values.entry(column.clone()).or_insert_with(|| vec![0.0; n])[wi] = *v;
+        }
+    }
+
+    Ok((values, op_name.unwrap_or_default()))
+}
+
+/// Remove a known column's per-worker values (all zeros if no worker reported it).
+fn take_column(values: &mut BTreeMap<String, Vec<f64>>, column: &str, n: usize) -> Vec<f64> {
+    values.remove(column).unwrap_or_else(|| vec![0.0; n])
+}
+
+/// Stats for the columns left over after the known ones were taken.
+fn extra_stats(values: BTreeMap<String, Vec<f64>>) -> BTreeMap<String, Stats> {
+    values
+        .into_iter()
+        .map(|(column, v)| (column, Stats::from_values(&v)))
+        .collect()
+}
+
+fn aggregate_time(
+    workers: &[RawIndex],
+    files: &[String],
+    diags: &mut Diagnostics,
+) -> Result<TimeIndex> {
     let n = workers.len();
     let all_addrs: BTreeSet<&Addr> = workers.iter().flat_map(|w| w.keys()).collect();
 
     let mut out = TimeIndex::new();
     for addr in all_addrs {
-        let mut activations = Vec::with_capacity(n);
-        let mut ms = Vec::with_capacity(n);
-        let mut op_name: Option<String> = None;
-
-        for (wi, w) in workers.iter().enumerate() {
-            match w.get(addr) {
-                Some(row) => {
-                    validate_op_name(&mut op_name, &row.op_name, addr, &files[0], &files[wi])?;
-                    activations.push(row.activations as f64);
-                    ms.push(row.total_active_ms);
-                }
-                None => {
-                    activations.push(0.0);
-                    ms.push(0.0);
-                }
-            }
-        }
+        let (mut values, op_name) = worker_values(workers, addr, files, diags)?;
+        let activations = take_column(&mut values, "activations", n);
+        let ms = take_column(&mut values, "total_active_ms", n);
 
         out.insert(
             addr.clone(),
             TimeRow {
                 activations: Stats::from_values(&activations),
                 total_active_ms: Stats::from_values(&ms),
-                op_name: op_name.unwrap_or_default(),
+                activations_by_worker: activations,
+                total_active_ms_by_worker: ms,
+                extra: extra_stats(values),
+                op_name,
                 num_workers: n,
             },
         );
@@ -184,38 +376,22 @@ fn aggregate_time(workers: &[RawTimeIndex], files: &[String]) -> Result<TimeInde
     Ok(out)
 }
 
-fn aggregate_memory(workers: &[RawMemoryIndex], files: &[String]) -> Result<MemoryIndex> {
+fn aggregate_memory(
+    workers: &[RawIndex],
+    files: &[String],
+    diags: &mut Diagnostics,
+) -> Result<MemoryIndex> {
     let n = workers.len();
     let all_addrs: BTreeSet<&Addr> = workers.iter().flat_map(|w| w.keys()).collect();
 
     let mut out = MemoryIndex::new();
     for addr in all_addrs {
-        let mut batched_in = Vec::with_capacity(n);
-        let mut merges = Vec::with_capacity(n);
-        let mut merge_in = Vec::with_capacity(n);
-        let mut merge_out = Vec::with_capacity(n);
-        let mut dropped = Vec::with_capacity(n);
-        let mut op_name: Option<String> = None;
-
-        for (wi, w) in workers.iter().enumerate() {
-            match w.get(addr) {
-                Some(row) => {
-                    validate_op_name(&mut op_name, &row.op_name, addr, &files[0], &files[wi])?;
-                    batched_in.push(row.batched_in as f64);
-                    merges.push(row.merges as f64);
-                    merge_in.push(row.merge_in as f64);
-                    merge_out.push(row.merge_out as f64);
-                    dropped.push(row.dropped as f64);
-                }
-                None => {
-                    batched_in.push(0.0);
-                    merges.push(0.0);
-                    merge_in.push(0.0);
-                    merge_out.push(0.0);
-                    dropped.push(0.0);
-                }
-            }
-        }
+        let (mut values, op_name) = worker_values(workers, addr, files, diags)?;
+        let batched_in = take_column(&mut values, "batched_in", n);
+        let merges = take_column(&mut values, "merges", n);
+        let merge_in = take_column(&mut values, "merge_in", n);
+        let merge_out = take_column(&mut values, "merge_out", n);
+        let dropped = take_column(&mut values, "dropped", n);
 
         out.insert(
             addr.clone(),
@@ -225,7 +401,13 @@ fn aggregate_memory(workers: &[RawMemoryIndex], files: &[String]) -> Result<Memo
                 merge_in: Stats::from_values(&merge_in),
                 merge_out: Stats::from_values(&merge_out),
                 dropped: Stats::from_values(&dropped),
-                op_name: op_name.unwrap_or_default(),
+                batched_in_by_worker: batched_in,
+                merges_by_worker: merges,
+                merge_in_by_worker: merge_in,
+                merge_out_by_worker: merge_out,
+                dropped_by_worker: dropped,
+                extra: extra_stats(values),
+                op_name,
                 num_workers: n,
             },
         );
@@ -239,8 +421,10 @@ fn aggregate_memory(workers: &[RawMemoryIndex], files: &[String]) -> Result<Memo
 // ---------------------------------------------------------------------------
 
 /// Regex to detect timestamped filenames like `*_tN_*.log`.
-static TIMESTAMP_RE: LazyLock<Regex> =
-    LazyLock::new(|| Regex::new(r"_t(\d+)_").unwrap());
+static TIMESTAMP_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"_t(\d+)_").unwrap());
+
+/// Regex to extract the trailing worker id from filenames like `time_worker_t0_17.log`.
+static WORKER_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"_(\d+)\.log$").unwrap());
 
 fn collect_log_files(dir: &str) -> Result<Vec<String>> {
     let path = Path::new(dir);
@@ -257,22 +441,26 @@ fn collect_log_files(dir: &str) -> Result<Vec<String>> {
     {
         let entry = entry?;
         let p = entry.path();
-        if p.is_file() {
-            if let Some(ext) = p.extension() {
-                if ext == "log" {
-                    files.push(p.to_string_lossy().to_string());
-                }
-            }
+        if p.is_file() && p.extension().is_some_and(|ext| ext == "log") {
+            files.push(p.to_string_lossy().to_string());
         }
     }
     files.sort();
     Ok(files)
 }
 
-/// Group files by timestamp `_tN_` in the filename. Returns sorted `(label, files)` pairs.
-fn group_by_timestamp(files: &[String]) -> Vec<(String, Vec<String>)> {
+/// Group files by timestamp `_tN_` in the filename and identify each file's worker.
+///
+/// Returns sorted `(label, [(worker, file)])` pairs with workers in ascending numeric
+/// order (so `_2.log` comes before `_10.log`). A file without a worker id is only
+/// accepted when it is alone in its snapshot (treated as worker 0); two files that
+/// claim the same worker in the same snapshot are an error.
+fn group_by_timestamp(
+    files: &[String],
+    diags: &mut Diagnostics,
+) -> Result<Vec<(String, WorkerFiles)>> {
     let re = &*TIMESTAMP_RE;
-    let mut groups: BTreeMap<u64, Vec<String>> = BTreeMap::new();
+    let mut groups: BTreeMap<u64, Vec<(Option<u32>, String)>> = BTreeMap::new();
 
     for f in files {
         let filename = Path::new(f)
@@ -283,136 +471,130 @@ fn group_by_timestamp(files: &[String]) -> Vec<(String, Vec<String>)> {
             .captures(&filename)
             .and_then(|c| c[1].parse::<u64>().ok())
             .unwrap_or(0);
-        groups.entry(ts).or_default().push(f.clone());
+        groups
+            .entry(ts)
+            .or_default()
+            .push((worker_id_from_filename(f), f.clone()));
     }
 
-    groups
-        .into_iter()
-        .map(|(ts, files)| (format!("t{}", ts), files))
-        .collect()
+    let mut out = Vec::new();
+    for (ts, group) in groups {
+        let label = format!("t{}", ts);
+        let single = group.len() == 1;
+        let mut by_worker: BTreeMap<u32, String> = BTreeMap::new();
+        for (worker, f) in group {
+            // In lenient mode, files that cannot be identified are skipped.
+            let worker = match worker {
+                Some(w) => w,
+                None if single => 0,
+                None => {
+                    diags.error(
+                        Some(Location::file(&f)),
+                        "cannot determine worker id (expected a name like *_<worker>.log)",
+                    )?;
+                    continue;
+                }
+            };
+            if let Some(prev) = by_worker.get(&worker) {
+                diags.error(
+                    Some(Location::file(&f)),
+                    format!(
+                        "duplicate worker {} in snapshot {} (already read from {})",
+                        worker, label, prev
+                    ),
+                )?;
+                continue;
+            }
+            by_worker.insert(worker, f);
+        }
+        out.push((label, by_worker.into_iter().collect()));
+    }
+    Ok(out)
 }
 
-// ---------------------------------------------------------------------------
-// Compiled regexes (compiled once)
-// ---------------------------------------------------------------------------
-
-static TIME_RE: LazyLock<Regex> = LazyLock::new(|| {
-    Regex::new(r#"^\s*(\[[^\]]*\])\s+(\d+)\s+([0-9]+(?:\.[0-9]+)?)\s+(.*?)\s*$"#).unwrap()
-});
-
-static MEMORY_RE: LazyLock<Regex> = LazyLock::new(|| {
-    Regex::new(r#"^\s*(\[[^\]]*\])\s+(\d+)\s+(\d+)\s+(\d+)\s+(\d+)\s+(\d+)\s+(.*?)\s*$"#)
-        .unwrap()
-});
+/// `(worker id, file path)` pairs of one snapshot, ordered by worker id.
+type WorkerFiles = Vec<(u32, String)>;
+
+/// Worker id encoded at the end of a log filename, if any.
+fn worker_id_from_filename(path: &str) -> Option<u32> {
+    let filename = Path::new(path)
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy();
+    WORKER_RE
+        .captures(&filename)
+        .and_then(|c| c[1].parse::<u32>().ok())
+}
 
 // ---------------------------------------------------------------------------
-// Raw single-file parsers (internal)
+// Raw single-file parser (internal)
 // ---------------------------------------------------------------------------
 
-fn parse_raw_time_file(path: &str) -> Result<RawTimeIndex> {
-    let text = fs::read_to_string(path)
-        .with_context(|| diagnostics::error_message(format!("read time log file {}", path)))?;
-
-    let re = &*TIME_RE;
-
-    let mut out = RawTimeIndex::new();
-    for (lineno, line) in text.lines().enumerate() {
-        let lno = lineno + 1;
-        let line = line.trim_end();
+/// Column layout of a log file, read from its header row `addr <metric>... name`.
+#[derive(Debug, Clone)]
+struct Columns {
+    /// Numeric columns between `addr` and `name`, in file order.
+    metrics: Vec<String>,
+}
 
-        if line.trim().is_empty() {
-            continue;
+impl Columns {
+    /// Parse a header row; `None` if the line is not a header.
+    fn from_header(line: &str) -> Option<Self> {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.as_slice() {
+            ["addr", metrics @ .., "name"] => Some(Self {
+                metrics: metrics.iter().map(|m| m.to_string()).collect(),
+            }),
+            _ => None,
         }
+    }
 
-        if line.contains("addr") && line.contains("activations") && line.contains("total_active_ms")
-        {
-            continue;
+    fn known(columns: &[&str]) -> Self {
+        Self {
+            metrics: columns.iter().map(|c| c.to_string()).collect(),
         }
+    }
+}
 
-        let caps = match re.captures(line) {
-            Some(c) => c,
-            None => {
-                bail!(
-                    "{}",
-                    diagnostics::error_message(format!(
-                        "time log parse error at {}:{}: cannot parse line: {:?}",
-                        path, lno, line
-                    ))
-                );
-            }
-        };
-
-        let addr_str = caps
-            .get(1)
-            .ok_or_else(|| {
-                anyhow!(diagnostics::error_message(format!(
-                    "time log parse error at {}:{}: missing addr",
-                    path, lno
-                )))
-            })?
-            .as_str();
-        let activations: u64 = caps
-            .get(2)
-            .ok_or_else(|| {
-                anyhow!(diagnostics::error_message(format!(
-                    "time log parse error at {}:{}: missing activations",
-                    path, lno
-                )))
-            })?
-            .as_str()
-            .parse()?;
-        let total_active_ms: f64 = caps
-            .get(3)
-            .ok_or_else(|| {
-                anyhow!(diagnostics::error_message(format!(
-                    "time log parse error at {}:{}: missing total_active_ms",
-                    path, lno
-                )))
-            })?
-            .as_str()
-            .parse()?;
-        let op_name = caps
-            .get(4)
-            .ok_or_else(|| {
-                anyhow!(diagnostics::error_message(format!(
-                    "time log parse error at {}:{}: missing name",
-                    path, lno
-                )))
-            })?
-            .as_str()
-            .to_string();
-
-        let addr = parse_addr(addr_str).with_context(|| {
-            diagnostics::error_message(format!("bad addr at {}:{}: {}", path, lno, addr_str))
-        })?;
-
-        let row = RawTimeRow {
-            activations,
-            total_active_ms,
-            op_name,
-        };
-
-        if out.insert(addr.clone(), row).is_some() {
-            bail!(
-                "{}",
-                diagnostics::error_message(format!(
-                    "duplicate addr entry in time log at {}:{}: {}",
-                    path, lno, addr_str
-                ))
-            );
-        }
+/// Split a data row into `(addr, metric tokens, name)`. The addr is the leading
+/// bracketed list, followed by exactly `n` whitespace-separated metric tokens; the
+/// rest of the line is the operator name (which may contain spaces).
+fn split_row(line: &str, n: usize) -> Option<(&str, Vec<&str>, &str)> {
+    let line = line.trim();
+    if !line.starts_with('[') {
+        return None;
+    }
+    let close = line.find(']')?;
+    let addr = &line[..=close];
+
+    let mut rest = &line[close + 1..];
+    let mut metrics = Vec::with_capacity(n);
+    for _ in 0..n {
+        rest = rest.trim_start();
+        let end = rest.find(char::is_whitespace)?;
+        metrics.push(&rest[..end]);
+        rest = &rest[end..];
     }
 
-    Ok(out)
+    let name = rest.trim();
+    if name.is_empty() {
+        return None;
+    }
+    Some((addr, metrics, name))
 }
 
-fn parse_raw_memory_file(path: &str) -> Result<RawMemoryIndex> {
+/// Parse one worker's log file. Files without a header row use `known_columns`.
+fn parse_raw_file(
+    path: &str,
+    kind: &str,
+    known_columns: &[&str],
+    diags: &mut Diagnostics,
+) -> Result<RawIndex> {
     let text = fs::read_to_string(path)
-        .with_context(|| diagnostics::error_message(format!("read memory file {}", path)))?;
+        .with_context(|| diagnostics::error_message(format!("read {} log file {}", kind, path)))?;
 
-    let re = &*MEMORY_RE;
-
-    let mut out = RawMemoryIndex::new();
+    let mut columns = Columns::known(known_columns);
+    let mut out = RawIndex::new();
     for (lineno, line) in text.lines().enumerate() {
         let lno = lineno + 1;
         let line = line.trim_end();
@@ -421,72 +603,104 @@ fn parse_raw_memory_file(path: &str) -> Result<RawMemoryIndex> {
             continue;
         }
 
-        if line.contains("addr") && line.contains("batched_in") {
+        // Every problem below skips the line in lenient mode.
+        let at = Some(Location::line(path, lno));
+
+        if let Some(header) = Columns::from_header(line) {
+            let missing: Vec<&str> = known_columns
+                .iter()
+                .filter(|c| !header.metrics.iter().any(|m| m == *c))
+                .copied()
+                .collect();
+            if !missing.is_empty() {
+                diags.error(
+                    at,
+                    format!("{} log header is missing column(s) {:?}", kind, missing),
+                )?;
+            }
+            // Lenient mode still maps by this header; missing columns read as 0.
+            columns = header;
             continue;
         }
 
-        let caps = match re.captures(line) {
-            Some(c) => c,
-            None => {
-                bail!(
-                    "{}",
-                    diagnostics::error_message(format!(
-                        "memory parse error at {}:{}: cannot parse line: {:?}",
-                        path, lno, line
-                    ))
-                );
-            }
+        let Some((addr_str, tokens, op_name)) = split_row(line, columns.metrics.len()) else {
+            diags.error(
+                at,
+                format!(
+                    "{} log parse error: expected addr, {} numeric column(s) and a name: {:?}",
+                    kind,
+                    columns.metrics.len(),
+                    line
+                ),
+            )?;
+            continue;
         };
 
-        macro_rules! get_u64 {
-            ($idx:expr, $field:literal) => {
-                caps.get($idx)
-                    .ok_or_else(|| {
-                        anyhow!(diagnostics::error_message(format!(
-                            "memory parse error at {}:{}: missing {}",
-                            path, lno, $field
-                        )))
-                    })?
-                    .as_str()
-                    .parse::<u64>()?
-            };
+        let parsed: std::result::Result<Vec<f64>, _> =
+            tokens.iter().map(|t| t.parse::<f64>()).collect();
+        let Ok(parsed) = parsed else {
+            diags.error(
+                at,
+                format!("{} log parse error: bad number in line: {:?}", kind, line),
+            )?;
+            continue;
+        };
+        let invalid = columns
+            .metrics
+            .iter()
+            .zip(&parsed)
+            .find_map(|(column, &v)| Some((column, invalid_value(column, v, known_columns)?)));
+        if let Some((column, problem)) = invalid {
+            diags.error(
+                at,
+                format!(
+                    "{} log parse error: {} {} in line: {:?}",
+                    kind, column, problem, line
+                ),
+            )?;
+            continue;
         }
 
-        let addr_str = caps.get(1).unwrap().as_str();
-        let batched_in = get_u64!(2, "batched_in");
-        let merges = get_u64!(3, "merges");
-        let merge_in = get_u64!(4, "merge_in");
-        let merge_out = get_u64!(5, "merge_out");
-        let dropped = get_u64!(6, "dropped");
-        let op_name = caps.get(7).unwrap().as_str().to_string();
-
-        let addr = parse_addr(addr_str).with_context(|| {
-            diagnostics::error_message(format!("bad addr at {}:{}: {}", path, lno, addr_str))
-        })?;
-
-        let row = RawMemoryRow {
-            batched_in,
-            merges,
-            merge_in,
-            merge_out,
-            dropped,
-            op_name,
+        let addr = match parse_addr(addr_str) {
+            Ok(addr) => addr,
+            Err(_) => {
+                diags.error(at, format!("bad addr: {}", addr_str))?;
+                continue;
+            }
         };
 
-        if out.insert(addr.clone(), row).is_some() {
-            bail!(
-                "{}",
-                diagnostics::error_message(format!(
-                    "duplicate addr entry in memory log at {}:{}: {}",
-                    path, lno, addr_str
-                ))
-            );
+        if out.contains_key(&addr) {
+            diags.error(
+                at,
+                format!("duplicate addr entry in {} log: {}", kind, addr_str),
+            )?;
+            continue;
         }
+        out.insert(
+            addr,
+            RawRow {
+                values: columns.metrics.iter().cloned().zip(parsed).collect(),
+                op_name: op_name.to_string(),
+            },
+        );
     }
 
     Ok(out)
 }
 
+/// Why a metric value is unusable, if it is: every value must be finite (NaN does
+/// not survive a JSON export), and the known columns, which are counters and
+/// durations, must not be negative.
+fn invalid_value(column: &str, value: f64, known_columns: &[&str]) -> Option<&'static str> {
+    if !value.is_finite() {
+        Some("is not a finite number")
+    } else if value < 0.0 && known_columns.contains(&column) {
+        Some("is negative")
+    } else {
+        None
+    }
+}
+
 /// Parse "[0, 8, 10]" into Addr(vec![0, 8, 10]).
 fn parse_addr(s: &str) -> Result<Addr> {
     let inner = s
@@ -516,3 +730,87 @@ fn parse_addr(s: &str) -> Result<Addr> {
     }
     Ok(Addr::new(v))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    /// A parsed row: addr and values by column name.
+    type Row = (Vec<u32>, Vec<f64>);
+
+    /// Parse `text` as one worker's time log, leniently; returns the rows kept and
+    /// the lines of the errors reported.
+    fn parse_time(test: &str, text: &str) -> (Vec<Row>, Vec<usize>) {
+        let path = std::env::temp_dir().join(format!(
+            "flowlog-profile-viz-{}-{}.log",
+            test,
+            std::process::id()
+        ));
+        fs::write(&path, text).unwrap();
+        let mut diags = Diagnostics::discard();
+        let index = parse_raw_file(path.to_str().unwrap(), "time", TIME_COLUMNS, &mut diags);
+        fs::remove_file(&path).unwrap();
+
+        let rows = index
+            .unwrap()
+            .into_iter()
+            .map(|(addr, row)| (addr.0, row.values.into_values().collect()))
+            .collect();
+        let lines = diags.items().iter().filter_map(|d| d.line).collect();
+        (rows, lines)
+    }
+
+    #[test]
+    fn header_lists_the_metric_columns() {
+        let columns = Columns::from_header("addr  activations  total_active_ms  name").unwrap();
+        assert_eq!(columns.metrics, vec!["activations", "total_active_ms"]);
+        assert!(
+            Columns::from_header("addr name")
+                .unwrap()
+                .metrics
+                .is_empty()
+        );
+        assert!(Columns::from_header("[0, 1]  3  1.5  Map").is_none());
+        assert!(Columns::from_header("addr activations total_active_ms").is_none());
+    }
+
+    #[test]
+    fn rows_split_into_addr_metrics_and_name() {
+        assert_eq!(
+            split_row("  [0, 8, 10]   12  3.5   Arrange: Threshold ", 2),
+            Some(("[0, 8, 10]", vec!["12", "3.5"], "Arrange: Threshold"))
+        );
+        // The name is required, and so is every metric.
+        assert_eq!(split_row("[0, 1]  12  3.5", 2), None);
+        assert_eq!(split_row("[0, 1]  12  Map", 2), None);
+        assert_eq!(split_row("0, 1  12  3.5  Map", 2), None);
+    }
+
+    #[test]
+    fn worker_id_is_the_trailing_number_of_the_filename() {
+        assert_eq!(
+            worker_id_from_filename("logs/time_worker_t0_17.log"),
+            Some(17)
+        );
+        assert_eq!(worker_id_from_filename("time_worker_3.log"), Some(3));
+        assert_eq!(worker_id_from_filename("logs_2/time.log"), None);
+        assert_eq!(worker_id_from_filename("time_worker_t0_1.txt"), None);
+    }
+
+    #[test]
+    fn rejects_non_finite_and_negative_counters() {
+        let text = "\
+addr    activations  total_active_ms  spill  name
+[0, 1]  3            1.5              -2     Map
+[0, 2]  NaN          1.5              0      Map
+[0, 3]  3            inf              0      Map
+[0, 4]  -1           1.5              0      Map
+[0, 5]  3            1.5              NaN    Map
+";
+        let (rows, error_lines) = parse_time("invalid-values", text);
+        // A negative extra column is not a counter, so it is kept.
+        assert_eq!(rows, vec![(vec![0, 1], vec![3.0, -2.0, 1.5])]);
+        assert_eq!(error_lines, vec![3, 4, 5, 6]);
+    }
+}
@@ -1,14 +1,28 @@
 use anyhow::{Context, bail};
-use clap::Parser;
+use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
 use std::collections::BTreeMap;
 use std::fs;
+use std::process::ExitCode;
 
 mod addr;
+mod check;
+mod datalog;
+mod delta;
 mod diagnostics;
+mod diff;
+mod export;
+mod folded;
 mod log;
 mod ops;
+mod payload;
 mod render;
+mod runs;
+mod scope;
+mod series;
 mod stats;
+mod summary;
+mod tables;
+mod trace;
 mod view;
 
 pub type Result<T> = anyhow::Result<T>;
@@ -16,96 +30,664 @@ pub type Result<T> = anyhow::Result<T>;
 #[derive(Parser)]
 #[command(name = "flowlog-profile-viz")]
 #[command(about = "FlowLog profile visualizer", long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Cli {
-    /// Path to the ops.json spec.
-    #[arg(short = 'p', long)]
-    ops: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    report: ReportArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Compare two runs (base vs new) and write a diff report.
+    Diff(DiffArgs),
+    /// Check a run against a stored baseline; exits with code 3 on a regression.
+    Check(CheckArgs),
+    /// Print the top strata, rules, nodes and operators of a run to the terminal.
+    Summary(SummaryArgs),
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Format {
+    /// The self-contained HTML report.
+    Html,
+    /// The analysis model as versioned JSON (see `export`).
+    Json,
+    /// Flat node, operator and rule tables, comma-separated (see `tables`).
+    Csv,
+    /// The same tables, tab-separated.
+    Tsv,
+    /// Folded stacks of the last snapshot, for flame graph tools (see `folded`).
+    Folded,
+    /// Chrome Trace Event Format JSON of the snapshot intervals, for Perfetto (see
+    /// `trace`).
+    Trace,
+}
+
+/// Render one run (the default, without a subcommand).
+#[derive(Args)]
+#[command(group(ArgGroup::new("logs").required(true).multiple(true).args(["time", "memory"])))]
+struct ReportArgs {
+    /// Path to the ops.json spec. Give it once, or once per run when averaging
+    /// repeated runs.
+    #[arg(short = 'p', long, required = true)]
+    ops: Vec<String>,
 
     /// Path to the folder containing time log files (*.log).
+    /// At least one of --time and --memory is required. Repeat it to average
+    /// several runs of the same program.
+    #[arg(short = 't', long)]
+    time: Vec<String>,
+
+    /// Path to the folder containing memory log files (*.log). Repeat it like --time.
+    #[arg(short = 'm', long)]
+    memory: Vec<String>,
+
+    /// Output file: the HTML report, or the JSON of --format json or trace. With
+    /// --format csv, tsv or folded, a directory for the tables or stacks.
+    #[arg(short = 'o', long, required = true)]
+    out: Option<String>,
+
+    /// Output format.
+    #[arg(long, value_enum, default_value_t = Format::Html)]
+    format: Format,
+
+    /// Skip malformed lines, duplicate entries and bad references instead of failing;
+    /// every skipped problem is listed in the report and in a final stderr summary.
+    #[arg(long)]
+    lenient: bool,
+
+    /// Gzip the embedded report data (base64-encoded); much smaller files for big
+    /// runs. Needs a browser with `DecompressionStream`.
+    #[arg(long)]
+    compress: bool,
+
+    /// How to attribute the cost of a node shared by several rules to those rules.
+    #[arg(long, value_enum, default_value_t = view::SharedPolicy::Split)]
+    shared_policy: view::SharedPolicy,
+}
+
+/// Compare the last snapshot of two runs.
+#[derive(Args)]
+#[command(group(ArgGroup::new("base_logs").required(true).multiple(true).args(["base_time", "base_memory", "base_export"])))]
+#[command(group(ArgGroup::new("new_logs").required(true).multiple(true).args(["time", "memory", "export"])))]
+struct DiffArgs {
+    /// ops.json of the base run (once, or once per repeated run).
+    #[arg(long, required_unless_present = "base_export")]
+    base_ops: Vec<String>,
+
+    /// Time log folder of the base run; repeat it for repeated runs.
+    #[arg(long)]
+    base_time: Vec<String>,
+
+    /// Memory log folder of the base run; repeat it for repeated runs.
+    #[arg(long)]
+    base_memory: Vec<String>,
+
+    /// JSON export (--format json) of the base run, instead of its ops.json and logs.
+    #[arg(long, conflicts_with_all = ["base_ops", "base_time", "base_memory"])]
+    base_export: Option<String>,
+
+    /// ops.json of the new run (once, or once per repeated run).
+    #[arg(short = 'p', long, required_unless_present = "export")]
+    ops: Vec<String>,
+
+    /// Time log folder of the new run; repeat it for repeated runs.
     #[arg(short = 't', long)]
-    time: String,
+    time: Vec<String>,
 
-    /// Path to the folder containing memory log files (*.log).
+    /// Memory log folder of the new run; repeat it for repeated runs.
     #[arg(short = 'm', long)]
-    memory: String,
+    memory: Vec<String>,
+
+    /// JSON export (--format json) of the new run, instead of its ops.json and logs.
+    #[arg(long, conflicts_with_all = ["ops", "time", "memory"])]
+    export: Option<String>,
 
     /// Output HTML file.
     #[arg(short = 'o', long)]
     out: String,
+
+    /// Confidence level of the per-delta significance tests (Welch's t-test over
+    /// workers), between 0 and 1.
+    #[arg(long, default_value_t = 0.95, value_parser = confidence_level)]
+    confidence: f64,
+
+    /// See the default command.
+    #[arg(long)]
+    lenient: bool,
+
+    /// See the default command. Only rule costs depend on it.
+    #[arg(long, value_enum, default_value_t = view::SharedPolicy::Split)]
+    shared_policy: view::SharedPolicy,
+}
+
+/// Regression gate against a baseline: a JSON export of a known-good run, e.g. one
+/// written by an earlier `check --update`.
+#[derive(Args)]
+#[command(group(ArgGroup::new("logs").required(true).multiple(true).args(["time", "memory", "export"])))]
+struct CheckArgs {
+    /// Baseline JSON export (--format json); its last snapshot is compared. Written
+    /// with --update.
+    #[arg(short = 'b', long)]
+    baseline: String,
+
+    /// ops.json of the run (once, or once per repeated run).
+    #[arg(short = 'p', long, required_unless_present = "export")]
+    ops: Vec<String>,
+
+    /// Time log folder of the run; repeat it for repeated runs.
+    #[arg(short = 't', long)]
+    time: Vec<String>,
+
+    /// Memory log folder of the run; repeat it for repeated runs.
+    #[arg(short = 'm', long)]
+    memory: Vec<String>,
+
+    /// JSON export (--format json) of the run, instead of its ops.json and logs.
+    #[arg(long, conflicts_with_all = ["ops", "time", "memory"])]
+    export: Option<String>,
+
+    /// Write the run's last snapshot as the new baseline export instead of checking.
+    #[arg(long)]
+    update: bool,
+
+    /// Also write the diff against the baseline as HTML.
+    #[arg(short = 'o', long, conflicts_with = "update")]
+    out: Option<String>,
+
+    /// Maximum growth of the total mapped time, in percent.
+    #[arg(long, default_value_t = 10.0)]
+    max_total_time_pct: f64,
+
+    /// Maximum growth of any node's time, in percent...
+    #[arg(long, default_value_t = 25.0)]
+    max_node_time_pct: f64,
+
+    /// ...counted only when the node also grew by more than this many ms.
+    #[arg(long, default_value_t = 50.0)]
+    min_node_ms: f64,
+
+    /// Maximum growth of the total batched_in, in percent.
+    #[arg(long, default_value_t = 20.0)]
+    max_memory_pct: f64,
+
+    /// Confidence level of the per-delta significance tests (Welch's t-test over
+    /// workers), between 0 and 1.
+    #[arg(long, default_value_t = 0.95, value_parser = confidence_level)]
+    confidence: f64,
+
+    /// See the default command.
+    #[arg(long)]
+    lenient: bool,
+
+    /// See the default command. Only rule costs depend on it.
+    #[arg(long, value_enum, default_value_t = view::SharedPolicy::Split)]
+    shared_policy: view::SharedPolicy,
+}
+
+/// Terminal summary of the last snapshot of a run.
+#[derive(Args)]
+#[command(group(ArgGroup::new("logs").required(true).multiple(true).args(["time", "memory", "export"])))]
+struct SummaryArgs {
+    /// ops.json of the run (once, or once per repeated run).
+    #[arg(short = 'p', long, required_unless_present = "export")]
+    ops: Vec<String>,
+
+    /// Time log folder of the run; repeat it for repeated runs.
+    #[arg(short = 't', long)]
+    time: Vec<String>,
+
+    /// Memory log folder of the run; repeat it for repeated runs.
+    #[arg(short = 'm', long)]
+    memory: Vec<String>,
+
+    /// JSON export (--format json) of the run, instead of its ops.json and logs.
+    #[arg(long, conflicts_with_all = ["ops", "time", "memory"])]
+    export: Option<String>,
+
+    /// Metric to rank the tables by.
+    #[arg(long, value_enum, default_value_t = summary::Metric::Time)]
+    by: summary::Metric,
+
+    /// Rows per table.
+    #[arg(short = 'n', long, default_value_t = 10)]
+    top: usize,
+
+    /// See the default command.
+    #[arg(long)]
+    lenient: bool,
+
+    /// See the default command.
+    #[arg(long, value_enum, default_value_t = view::SharedPolicy::Split)]
+    shared_policy: view::SharedPolicy,
+}
+
+fn confidence_level(s: &str) -> std::result::Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(level) if level > 0.0 && level < 1.0 => Ok(level),
+        _ => Err(format!(
+            "`{}` is not a confidence level between 0 and 1 (e.g. 0.95)",
+            s
+        )),
+    }
+}
+
+/// One run loaded from disk (or several repeated runs averaged into one): one
+/// `ReportData` per snapshot label.
+struct Run {
+    program: view::Program,
+    /// Number of averaged runs (1 for a single run).
+    runs: usize,
+    time_snapshots: Vec<log::Snapshot<log::TimeIndex>>,
+    memory_snapshots: Vec<log::Snapshot<log::MemoryIndex>>,
+    labels: Vec<String>,
+    snapshots: Vec<view::ReportData>,
 }
 
-fn main() -> Result<()> {
-    let Cli {
+fn main() -> Result<ExitCode> {
+    let cli = Cli::parse();
+    match cli.command {
+        Some(Command::Diff(args)) => run_diff(args)?,
+        Some(Command::Check(args)) => return run_check(args),
+        Some(Command::Summary(args)) => run_summary(args)?,
+        None => run_report(cli.report)?,
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run_report(args: ReportArgs) -> Result<()> {
+    let ReportArgs {
         ops,
         time,
         memory,
         out,
-    } = Cli::parse();
-
-    // 1) Parse + validate ops.json.
-    let ops_text = fs::read_to_string(&ops)
-        .with_context(|| diagnostics::error_message(format!("read ops file {}", ops)))?;
-    let ops_spec: ops::OpsSpec = serde_json::from_str(&ops_text)
-        .with_context(|| diagnostics::error_message(format!("parse ops file {}", ops)))?;
-    let validated = ops_spec.validate_and_build()?;
-
-    let ops::ValidatedOps {
-        nodes,
-        roots: root_ids,
-        rules,
-        fingerprint_to_node,
-    } = validated;
-
-    let mut nodes_by_name = BTreeMap::new();
-    for (id, node) in nodes {
-        nodes_by_name.insert(id.to_string(), node);
-    }
-
-    let roots: Vec<String> = root_ids.iter().map(|id| id.to_string()).collect();
-
-    let fingerprint_to_node: BTreeMap<String, String> = fingerprint_to_node
-        .into_iter()
-        .map(|(fp, id)| (fp, id.to_string()))
-        .collect();
-
-    // 2) Parse time and memory log folders (auto-detects batch vs timestamped).
-    let time_snapshots = log::parse_time_folder(&time)?;
-    let memory_snapshots = log::parse_memory_folder(&memory)?;
-
-    // Validate that time and memory have the same snapshot labels.
-    let time_labels: Vec<&str> = time_snapshots.iter().map(|s| s.label.as_str()).collect();
-    let mem_labels: Vec<&str> = memory_snapshots.iter().map(|s| s.label.as_str()).collect();
-    if time_labels != mem_labels {
+        format,
+        lenient,
+        compress,
+        shared_policy,
+    } = args;
+    // clap enforces it (`required = true`); it is an option only so that a
+    // subcommand can be given instead.
+    let out = out.unwrap_or_default();
+    if compress && !matches!(format, Format::Html) {
+        bail!(
+            "{}",
+            diagnostics::error_message("--compress only applies to --format html")
+        );
+    }
+    let mut diags = diagnostics::Diagnostics::new(lenient);
+
+    let Run {
+        program,
+        runs,
+        time_snapshots,
+        memory_snapshots,
+        labels: snapshot_labels,
+        snapshots,
+    } = load_run(&ops, &time, &memory, shared_policy, &mut diags)?;
+
+    // Interval view: what happened between consecutive cumulative snapshots.
+    // Mapping problems were reported for the cumulative build, so they are not
+    // reported twice; only counter resets are new here.
+    let mut interval_snapshots: Vec<view::ReportData> = Vec::new();
+    if snapshot_labels.len() > 1 {
+        let time_intervals = delta::time_intervals(&time_snapshots, &mut diags);
+        let memory_intervals = delta::memory_intervals(&memory_snapshots, &mut diags);
+        let mut repeat = diagnostics::Diagnostics::discard();
+        for (_, ts, ms) in log::pair_snapshots(&time_intervals, &memory_intervals) {
+            interval_snapshots.push(view::build_report_data(
+                &program,
+                ts,
+                ms,
+                shared_policy,
+                &mut repeat,
+            )?);
+        }
+    }
+
+    // Render HTML, or export the model.
+    let write = |rendered: String| {
+        fs::write(&out, rendered)
+            .with_context(|| diagnostics::error_message(format!("write output file {}", out)))
+    };
+    match format {
+        Format::Html => {
+            let series = series::build_series(&snapshot_labels, &snapshots);
+            write(render::render_html_report(
+                &snapshot_labels,
+                &snapshots,
+                &interval_snapshots,
+                &series,
+                diags.items(),
+                compress,
+            )?)?
+        }
+        Format::Json => write(export::render_json(&export::Export::new(
+            &snapshot_labels,
+            &snapshots,
+            &interval_snapshots,
+            diags.items(),
+        ))?)?,
+        Format::Csv => tables::write_tables(&out, b',', "csv", &snapshot_labels, &snapshots)?,
+        Format::Tsv => tables::write_tables(&out, b'\t', "tsv", &snapshot_labels, &snapshots)?,
+        Format::Trace => {
+            // With one snapshot there are no interval snapshots: it is its own interval.
+            let intervals = if interval_snapshots.is_empty() {
+                &snapshots
+            } else {
+                &interval_snapshots
+            };
+            write(trace::render_trace(&snapshot_labels, intervals)?)?
+        }
+        Format::Folded => {
+            let Some(last) = snapshots.last() else {
+                bail!(
+                    "{}",
+                    diagnostics::error_message(
+                        "--format folded needs a snapshot, and the logs have none"
+                    )
+                );
+            };
+            folded::write_folded(&out, last)?
+        }
+    }
+    let averaged = if runs > 1 {
+        format!(", averaged over {} runs", runs)
+    } else {
+        String::new()
+    };
+    println!(
+        "Wrote {} ({} snapshot(s): {}{})",
+        out,
+        snapshot_labels.len(),
+        snapshot_labels.join(", "),
+        averaged
+    );
+    diags.print_summary();
+
+    Ok(())
+}
+
+fn run_diff(args: DiffArgs) -> Result<()> {
+    let mut diags = diagnostics::Diagnostics::new(args.lenient);
+    let (base_label, base_data) = load_last_snapshot(
+        "base",
+        &args.base_ops,
+        &args.base_time,
+        &args.base_memory,
+        args.base_export.as_deref(),
+        args.shared_policy,
+        &mut diags,
+    )?;
+    let (new_label, new_data) = load_last_snapshot(
+        "new",
+        &args.ops,
+        &args.time,
+        &args.memory,
+        args.export.as_deref(),
+        args.shared_policy,
+        &mut diags,
+    )?;
+
+    let report = diff::diff_reports(
+        &diff::RunSummary::new(&base_label, &base_data),
+        &diff::RunSummary::new(&new_label, &new_data),
+        args.confidence,
+    );
+    let html = render::render_diff_report(&report, diags.items())?;
+    fs::write(&args.out, html)
+        .with_context(|| diagnostics::error_message(format!("write output file {}", args.out)))?;
+    println!(
+        "Wrote {} (base {} vs new {})",
+        args.out, base_label, new_label
+    );
+    summary::print_diff(&report);
+    diags.print_summary();
+
+    Ok(())
+}
+
+fn run_check(args: CheckArgs) -> Result<ExitCode> {
+    let mut diags = diagnostics::Diagnostics::new(args.lenient);
+    let (label, data) = load_last_snapshot(
+        "checked",
+        &args.ops,
+        &args.time,
+        &args.memory,
+        args.export.as_deref(),
+        args.shared_policy,
+        &mut diags,
+    )?;
+
+    if args.update {
+        let baseline = export::Export::new(
+            std::slice::from_ref(&label),
+            std::slice::from_ref(&data),
+            &[],
+            diags.items(),
+        );
+        fs::write(&args.baseline, export::render_json(&baseline)?).with_context(|| {
+            diagnostics::error_message(format!("write baseline file {}", args.baseline))
+        })?;
+        println!("Wrote baseline {} (snapshot {})", args.baseline, label);
+        diags.print_summary();
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let (base_label, base_data) = load_last_snapshot(
+        "baseline",
+        &[],
+        &[],
+        &[],
+        Some(&args.baseline),
+        args.shared_policy,
+        &mut diags,
+    )?;
+    let base = diff::RunSummary::new(&base_label, &base_data);
+    let new = diff::RunSummary::new(&label, &data);
+    let report = diff::diff_reports(&base, &new, args.confidence);
+    let thresholds = check::Thresholds {
+        total_time_pct: args.max_total_time_pct,
+        node_time_pct: args.max_node_time_pct,
+        node_min_ms: args.min_node_ms,
+        memory_pct: args.max_memory_pct,
+    };
+    let outcome = check::check(&report, &thresholds, &mut diags);
+    if let Some(out) = &args.out {
+        let html = render::render_diff_report(&report, diags.items())?;
+        fs::write(out, html)
+            .with_context(|| diagnostics::error_message(format!("write output file {}", out)))?;
+        println!("Wrote {}", out);
+    }
+    summary::print_check(&base.label, &label, &outcome.violations, &outcome.noise);
+    diags.print_summary();
+
+    Ok(ExitCode::from(outcome.exit_code()))
+}
+
+fn run_summary(args: SummaryArgs) -> Result<()> {
+    let mut diags = diagnostics::Diagnostics::new(args.lenient);
+    let (label, data) = load_last_snapshot(
+        "summarized",
+        &args.ops,
+        &args.time,
+        &args.memory,
+        args.export.as_deref(),
+        args.shared_policy,
+        &mut diags,
+    )?;
+    let (log, present) = if args.by.is_memory() {
+        ("memory", data.has_memory)
+    } else {
+        ("time", data.has_time)
+    };
+    if !present {
         bail!(
             "{}",
             diagnostics::error_message(format!(
-                "time and memory folders have different snapshots: {:?} vs {:?}",
-                time_labels, mem_labels
+                "--by {} needs a {} log, and snapshot {} has none",
+                args.by.name(),
+                log,
+                label
             ))
         );
     }
+    summary::print_summary(&label, &data, args.by, args.top);
+    summary::print_critical_paths(&label, &data);
+    diags.print_summary();
+    Ok(())
+}
 
-    // 3) Build one ReportData per snapshot.
-    let mut snapshot_labels: Vec<String> = Vec::new();
-    let mut snapshots: Vec<view::ReportData> = Vec::new();
+/// The last snapshot of a run and its label, from the run's ops.json and logs, or
+/// from a JSON export of it (whose snapshots already embed their shared policy).
+fn load_last_snapshot(
+    side: &str,
+    ops: &[String],
+    time: &[String],
+    memory: &[String],
+    export: Option<&str>,
+    shared_policy: view::SharedPolicy,
+    diags: &mut diagnostics::Diagnostics,
+) -> Result<(String, view::ReportData)> {
+    let (mut labels, mut snapshots) = match export {
+        Some(path) => {
+            let export = export::read_export(path)?;
+            (export.snapshot_labels, export.snapshots)
+        }
+        None => {
+            let run = load_run(ops, time, memory, shared_policy, diags)?;
+            (run.labels, run.snapshots)
+        }
+    };
+    match (labels.pop(), snapshots.pop()) {
+        (Some(label), Some(data)) => Ok((label, data)),
+        _ => bail!(
+            "{}",
+            diagnostics::error_message(format!("{} run has no snapshots", side))
+        ),
+    }
+}
 
-    for (ts, ms) in time_snapshots.iter().zip(memory_snapshots.iter()) {
-        snapshot_labels.push(ts.label.clone());
+/// Parse and validate ops.json, parse the time and/or memory log folders
+/// (auto-detects batch vs timestamped), and build one `ReportData` per snapshot
+/// label found in either folder.
+///
+/// Several time (or memory) folders are repeated runs of one program: their logs
+/// are averaged per worker, and each snapshot also gets the run-to-run stats. `ops`
+/// is then given once, or once per run (checked to describe the same program).
+fn load_run(
+    ops: &[String],
+    time: &[String],
+    memory: &[String],
+    shared_policy: view::SharedPolicy,
+    diags: &mut diagnostics::Diagnostics,
+) -> Result<Run> {
+    let runs = time.len().max(memory.len());
+    if !time.is_empty() && !memory.is_empty() && time.len() != memory.len() {
+        bail!(
+            "{}",
+            diagnostics::error_message(format!(
+                "{} time folder(s) but {} memory folder(s); give one of each per run",
+                time.len(),
+                memory.len()
+            ))
+        );
+    }
+    if ops.len() != 1 && ops.len() != runs {
+        bail!(
+            "{}",
+            diagnostics::error_message(format!(
+                "{} ops file(s) for {} run(s); give one for all runs or one per run",
+                ops.len(),
+                runs
+            ))
+        );
+    }
+
+    let mut validated = Vec::new();
+    for path in ops {
+        let ops_text = fs::read_to_string(path)
+            .with_context(|| diagnostics::error_message(format!("read ops file {}", path)))?;
+        let ops_spec: ops::OpsSpec = serde_json::from_str(&ops_text)
+            .with_context(|| diagnostics::error_message(format!("parse ops file {}", path)))?;
+        validated.push(ops_spec.validate_and_build(path, diags)?);
+    }
+    for (path, other) in ops.iter().zip(&validated).skip(1) {
+        runs::check_same_program(&validated[0], other, path, diags)?;
+    }
+    let program = view::Program::new(validated.swap_remove(0), diags);
+
+    let mut time_runs = Vec::new();
+    for dir in time {
+        time_runs.push(log::parse_time_folder(dir, diags)?);
+    }
+    let mut memory_runs = Vec::new();
+    for dir in memory {
+        memory_runs.push(log::parse_memory_folder(dir, diags)?);
+    }
+    let (time_snapshots, memory_snapshots) = if runs > 1 {
+        (
+            runs::average_time(&time_runs, diags)?,
+            runs::average_memory(&memory_runs, diags)?,
+        )
+    } else {
+        (
+            time_runs.pop().unwrap_or_default(),
+            memory_runs.pop().unwrap_or_default(),
+        )
+    };
+
+    let mut labels: Vec<String> = Vec::new();
+    let mut snapshots: Vec<view::ReportData> = Vec::new();
+    let pairs = log::pair_snapshots(&time_snapshots, &memory_snapshots);
+    log::check_snapshot_pairs(&pairs, diags);
+    for (label, ts, ms) in pairs {
+        if let (Some(ts), Some(ms)) = (ts, ms) {
+            log::check_worker_sets_match(&label, &ts.workers, &ms.workers, diags);
+        }
         snapshots.push(view::build_report_data(
-            &nodes_by_name,
-            &roots,
-            &rules,
-            &fingerprint_to_node,
-            &ts.data,
-            &ms.data,
+            &program,
+            ts,
+            ms,
+            shared_policy,
+            diags,
         )?);
+        labels.push(label);
     }
 
-    // 4) Render HTML.
-    let html = render::render_html_report(&snapshot_labels, &snapshots)?;
-    fs::write(&out, html)
-        .with_context(|| diagnostics::error_message(format!("write output file {}", out)))?;
-    println!("Wrote {} ({} snapshot(s): {})", out, snapshot_labels.len(), snapshot_labels.join(", "));
+    // Each run on its own, for the run-to-run stats. Its problems were reported
+    // while averaging.
+    if runs > 1 {
+        let mut repeat = diagnostics::Diagnostics::discard();
+        let mut per_run: Vec<BTreeMap<String, view::ReportData>> = Vec::new();
+        for run in 0..runs {
+            let ts = time_runs.get(run).map(Vec::as_slice).unwrap_or_default();
+            let ms = memory_runs.get(run).map(Vec::as_slice).unwrap_or_default();
+            let mut by_label = BTreeMap::new();
+            for (label, ts, ms) in log::pair_snapshots(ts, ms) {
+                by_label.insert(
+                    label,
+                    view::build_report_data(&program, ts, ms, shared_policy, &mut repeat)?,
+                );
+            }
+            per_run.push(by_label);
+        }
+        for (label, data) in labels.iter().zip(&mut snapshots) {
+            let run_data: Vec<&view::ReportData> =
+                per_run.iter().filter_map(|r| r.get(label)).collect();
+            data.runs = Some(runs::build_runs_view(&run_data));
+        }
+    }
 
-    Ok(())
+    Ok(Run {
+        program,
+        runs,
+        time_snapshots,
+        memory_snapshots,
+        labels,
+        snapshots,
+    })
 }
@@ -21,7 +21,7 @@
 use crate::Result;
 use crate::addr::Addr;
 
-use crate::diagnostics;
+use crate::diagnostics::{self, Diagnostics, Location};
 
 use anyhow::bail;
 use serde::Deserialize;
@@ -94,6 +94,8 @@ pub struct NodeSpec {
 
 #[derive(Debug, Clone)]
 pub struct RulePlanNodeSpec {
+    /// Node the fingerprint maps to, in the block of the rule's plan.
+    pub node: u32,
     pub children: Vec<String>,
 }
 
@@ -111,18 +113,21 @@ impl OpsSpec {
     /// 1) Normalize node rows (dedup parents, normalize fingerprints).
     /// 2) Validate structural integrity (unique ids, parents exist, fingerprint rules).
     /// 3) Build rule plan trees (derive children, compute roots).
-    pub fn validate_and_build(&self) -> Result<ValidatedOps> {
+    ///
+    /// In lenient mode each problem is recorded in `diags` and the offending item is
+    /// dropped (a duplicate node, a dangling parent edge, a plan node, or a whole rule).
+    /// Diagnostics are located at `path`, the ops.json the spec was read from.
+    pub fn validate_and_build(&self, path: &str, diags: &mut Diagnostics) -> Result<ValidatedOps> {
+        let location = Some(Location::file(path));
         // Phase 1: build map keyed by id and normalize node fields.
         let mut nodes: BTreeMap<u32, NodeSpec> = BTreeMap::new();
         for raw in &self.nodes {
             if nodes.contains_key(&raw.id) {
-                bail!(
-                    "{}",
-                    diagnostics::error_message(format!(
-                        "duplicate node id in ops.json: {}",
-                        raw.id
-                    ))
-                );
+                diags.error(
+                    location,
+                    format!("duplicate node id in ops.json: {}", raw.id),
+                )?;
+                continue;
             }
 
             let block = raw.block.clone().unwrap_or_else(|| "other".to_string());
@@ -154,31 +159,51 @@ impl OpsSpec {
         if nodes.is_empty() {
             bail!(
                 "{}",
-                diagnostics::error_message("ops.json contained no nodes")
+                diagnostics::error_message(format!("{}: ops.json contained no nodes", path))
             );
         }
 
         // Phase 2a: enforce unique, non-empty fingerprints within the same block.
-        // We still record a global fingerprint->node mapping for rule validation.
+        // We also record a global fingerprint->node mapping (lowest id) for rule
+        // validation, and as a fallback for plans that do not lie in one block.
         let mut fingerprint_to_node: BTreeMap<String, u32> = BTreeMap::new();
         let mut fingerprint_block_to_node: BTreeMap<(String, String), u32> = BTreeMap::new();
         for (id, node) in &nodes {
             if let Some(fp) = &node.fingerprint {
                 let key = (node.block.clone(), fp.clone());
-                if let Some(prev) = fingerprint_block_to_node.insert(key.clone(), *id) {
-                    bail!(
-                        "{}",
-                        diagnostics::error_message(format!(
+                if let Some(prev) = fingerprint_block_to_node.get(&key) {
+                    diags.error(
+                        location,
+                        format!(
                             "fingerprint '{}' is used by multiple nodes in block '{}' ({} and {})",
                             fp, key.0, prev, id
-                        ))
-                    );
+                        ),
+                    )?;
+                    continue;
                 }
+                fingerprint_block_to_node.insert(key, *id);
                 fingerprint_to_node.entry(fp.clone()).or_insert(*id);
             }
         }
 
-        // Phase 2b: compute roots (nodes with no parents).
+        // Phase 2b: basic sanity—every parent id must exist (lenient: drop the edge).
+        let ids: BTreeSet<u32> = nodes.keys().copied().collect();
+        for node in nodes.values_mut() {
+            let mut kept = Vec::with_capacity(node.parents.len());
+            for pid in &node.parents {
+                if ids.contains(pid) {
+                    kept.push(*pid);
+                } else {
+                    diags.error(
+                        location,
+                        format!("node {} references missing parent id {}", node.id, pid),
+                    )?;
+                }
+            }
+            node.parents = kept;
+        }
+
+        // Phase 2c: compute roots (nodes with no parents).
         let mut roots: Vec<u32> = Vec::new();
         for (id, node) in &nodes {
             if node.parents.is_empty() {
@@ -188,75 +213,102 @@ impl OpsSpec {
         roots.sort();
         roots.dedup();
 
-        // Phase 2c: basic sanity—every parent id must exist.
-        for node in nodes.values() {
-            for pid in &node.parents {
-                if !nodes.contains_key(pid) {
-                    bail!(
-                        "{}",
-                        diagnostics::error_message(format!(
-                            "node {} references missing parent id {}",
-                            node.id, pid
-                        ))
-                    );
-                }
-            }
-        }
-
         // Phase 3: validate rules + plan trees (if provided).
         let mut rules_out: Vec<RuleSpec> = Vec::new();
         for raw_rule in &self.rules {
             let mut raw_parents: BTreeMap<String, Vec<String>> = BTreeMap::new();
             let mut nodes_map: BTreeMap<String, RulePlanNodeSpec> = BTreeMap::new();
 
+            // Lenient: bad plan entries are skipped.
             for pn in &raw_rule.plan_tree {
                 let fp = pn.fingerprint.trim();
                 if fp.is_empty() {
-                    bail!(
-                        "{}",
-                        diagnostics::error_message(format!(
-                            "rule '{}' has an empty fingerprint entry",
-                            raw_rule.text
-                        ))
-                    );
+                    diags.error(
+                        location,
+                        format!("rule '{}' has an empty fingerprint entry", raw_rule.text),
+                    )?;
+                    continue;
                 }
                 if nodes_map.contains_key(fp) {
-                    bail!(
-                        "{}",
-                        diagnostics::error_message(format!(
+                    diags.error(
+                        location,
+                        format!(
                             "rule '{}' has duplicate fingerprint '{}' in plan tree",
                             raw_rule.text, fp
-                        ))
-                    );
+                        ),
+                    )?;
+                    continue;
                 }
                 if !fingerprint_to_node.contains_key(fp) {
-                    bail!(
-                        "{}",
-                        diagnostics::error_message(format!(
+                    diags.error(
+                        location,
+                        format!(
                             "rule '{}' references fingerprint '{}' not found in any node",
                             raw_rule.text, fp
-                        ))
-                    );
+                        ),
+                    )?;
+                    continue;
                 }
 
                 let parents = normalize_parents(pn.parents.clone());
                 raw_parents.insert(fp.to_string(), parents);
             }
 
-            // Validate that all parents exist within the plan tree.
-            for (fp, parents) in &raw_parents {
-                for parent in parents {
-                    if !raw_parents.contains_key(parent) {
-                        bail!(
-                            "{}",
-                            diagnostics::error_message(format!(
+            // Fingerprints are unique per block only: resolve the plan in the block
+            // holding all of its fingerprints.
+            let mut blocks: Option<BTreeSet<&str>> = None;
+            for fp in raw_parents.keys() {
+                let with_fp: BTreeSet<&str> = fingerprint_block_to_node
+                    .keys()
+                    .filter(|(_, f)| f == fp)
+                    .map(|(block, _)| block.as_str())
+                    .collect();
+                blocks = Some(match blocks {
+                    Some(b) => &b & &with_fp,
+                    None => with_fp,
+                });
+            }
+            let block = match blocks.unwrap_or_default() {
+                b if b.len() == 1 => b.into_iter().next(),
+                b if !raw_parents.is_empty() => {
+                    diags.warning(
+                        location,
+                        format!(
+                            "rule '{}' plan does not map to a single block ({} candidates); \
+                             its fingerprints resolve to their lowest node id",
+                            raw_rule.text,
+                            b.len()
+                        ),
+                    );
+                    None
+                }
+                _ => None,
+            };
+            let node_of = |fp: &String| match block {
+                Some(block) => fingerprint_block_to_node[&(block.to_string(), fp.clone())],
+                None => fingerprint_to_node[fp],
+            };
+
+            // Validate that all parents exist within the plan tree (lenient: drop the edge).
+            let known: BTreeSet<String> = raw_parents.keys().cloned().collect();
+            for (fp, parents) in raw_parents.iter_mut() {
+                let mut kept = Vec::with_capacity(parents.len());
+                for parent in parents.iter() {
+                    if known.contains(parent) {
+                        kept.push(parent.clone());
+                    } else {
+                        diags.error(
+                            location,
+                            format!(
                                 "rule '{}' references parent fingerprint '{}' not present in its plan tree",
                                 raw_rule.text, parent
-                            ))
-                        );
+                            ),
+                        )?;
                     }
                 }
+                *parents = kept;
                 nodes_map.entry(fp.clone()).or_insert(RulePlanNodeSpec {
+                    node: node_of(fp),
                     children: Vec::new(),
                 });
             }
@@ -267,6 +319,7 @@ impl OpsSpec {
                     nodes_map
                         .entry(parent.clone())
                         .or_insert(RulePlanNodeSpec {
+                            node: node_of(parent),
                             children: Vec::new(),
                         })
                         .children
@@ -281,18 +334,20 @@ impl OpsSpec {
             // Compute sink (node with no children) and ensure exactly one.
             let sinks: Vec<String> = nodes_map
                 .iter()
-                .filter_map(|(fp, node)| node.children.is_empty().then(|| fp.clone()))
+                .filter(|(_, node)| node.children.is_empty())
+                .map(|(fp, _)| fp.clone())
                 .collect();
 
             if sinks.len() != 1 {
-                bail!(
-                    "{}",
-                    diagnostics::error_message(format!(
+                diags.error(
+                    location,
+                    format!(
                         "rule '{}' plan tree must have exactly one sink fingerprint (found {})",
                         raw_rule.text,
                         sinks.len()
-                    ))
-                );
+                    ),
+                )?;
+                continue;
             }
 
             let root_fp = sinks[0].clone();
@@ -304,22 +359,36 @@ impl OpsSpec {
             });
         }
 
-        // Phase 4: enforce that every fingerprinted node appears in rules.
+        // Phase 4: enforce that every fingerprinted node appears in rules, and warn
+        // about nodes whose fingerprint no rule plan resolves to (their cost is not
+        // attributed to any rule).
         let mut rule_fps: BTreeSet<String> = BTreeSet::new();
+        let mut rule_nodes: BTreeSet<u32> = BTreeSet::new();
         for rule in &rules_out {
             rule_fps.extend(rule.nodes.keys().cloned());
+            rule_nodes.extend(rule.nodes.values().map(|plan| plan.node));
         }
         for (id, node) in &nodes {
-            if let Some(fp) = &node.fingerprint {
-                if !rule_fps.contains(fp) {
-                    bail!(
-                        "{}",
-                        diagnostics::error_message(format!(
-                            "node {} has fingerprint '{}' but it is not recorded in rules",
-                            id, fp
-                        ))
-                    );
-                }
+            let Some(fp) = &node.fingerprint else {
+                continue;
+            };
+            if !rule_fps.contains(fp) {
+                diags.error(
+                    location,
+                    format!(
+                        "node {} has fingerprint '{}' but it is not recorded in rules",
+                        id, fp
+                    ),
+                )?;
+            } else if !rule_nodes.contains(id) {
+                diags.warning(
+                    location,
+                    format!(
+                        "node {} has fingerprint '{}' but no rule plan resolves to it; \
+                         its cost is not attributed to any rule",
+                        id, fp
+                    ),
+                );
             }
         }
 
@@ -327,7 +396,6 @@ impl OpsSpec {
             nodes,
             roots,
             rules: rules_out,
-            fingerprint_to_node,
         })
     }
 }
@@ -344,7 +412,6 @@ pub struct ValidatedOps {
     pub nodes: BTreeMap<u32, NodeSpec>,
     pub roots: Vec<u32>,
     pub rules: Vec<RuleSpec>,
-    pub fingerprint_to_node: BTreeMap<String, u32>,
 }
 
 fn deserialize_fingerprint<'de, D>(deserializer: D) -> std::result::Result<String, D::Error>
@@ -361,3 +428,104 @@ where
     }
     Ok(s)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::Severity;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    fn spec(value: serde_json::Value) -> OpsSpec {
+        serde_json::from_value(value).unwrap()
+    }
+
+    /// (fingerprint, resolved node) of each plan entry of `rule`.
+    fn plan_nodes(rule: &RuleSpec) -> Vec<(&str, u32)> {
+        rule.nodes
+            .iter()
+            .map(|(fp, plan)| (fp.as_str(), plan.node))
+            .collect()
+    }
+
+    #[test]
+    fn resolves_fingerprints_in_the_block_of_the_rule() {
+        // "x" is in both strata, "z" only in stratum 0 and "y" only in stratum 1.
+        let ops = spec(json!({
+            "nodes": [
+                { "id": 0, "block": "stratum 0", "fingerprint": "x" },
+                { "id": 1, "block": "stratum 1", "fingerprint": "x" },
+                { "id": 2, "block": "stratum 1", "fingerprint": "y", "parents": [1] },
+                { "id": 3, "block": "stratum 0", "fingerprint": "z", "parents": [0] },
+            ],
+            "rules": [
+                {
+                    "text": "a(x) :- e(x).",
+                    "plan_tree": [
+                        { "fingerprint": "x" },
+                        { "fingerprint": "z", "parents": ["x"] },
+                    ],
+                },
+                {
+                    "text": "b(x) :- a(x).",
+                    "plan_tree": [
+                        { "fingerprint": "x" },
+                        { "fingerprint": "y", "parents": ["x"] },
+                    ],
+                },
+            ],
+        }));
+        let mut diags = Diagnostics::new(false);
+        let validated = ops.validate_and_build("ops.json", &mut diags).unwrap();
+
+        assert_eq!(plan_nodes(&validated.rules[0]), vec![("x", 0), ("z", 3)]);
+        assert_eq!(plan_nodes(&validated.rules[1]), vec![("x", 1), ("y", 2)]);
+        assert_eq!(validated.rules[1].root, "y");
+        assert!(diags.items().is_empty());
+    }
+
+    #[test]
+    fn warns_about_a_fingerprinted_node_no_rule_resolves_to() {
+        // Nodes 0 and 1 share "x", but the only rule lies in stratum 0.
+        let ops = spec(json!({
+            "nodes": [
+                { "id": 0, "block": "stratum 0", "fingerprint": "x" },
+                { "id": 1, "block": "stratum 1", "fingerprint": "x" },
+                { "id": 2, "block": "stratum 0", "fingerprint": "z", "parents": [0] },
+            ],
+            "rules": [{
+                "text": "a(x) :- e(x).",
+                "plan_tree": [
+                    { "fingerprint": "x" },
+                    { "fingerprint": "z", "parents": ["x"] },
+                ],
+            }],
+        }));
+        // Strict mode: a warning, not an error.
+        let mut diags = Diagnostics::new(false);
+        let validated = ops.validate_and_build("ops.json", &mut diags).unwrap();
+
+        assert_eq!(plan_nodes(&validated.rules[0]), vec![("x", 0), ("z", 2)]);
+        let items: Vec<_> = diags
+            .items()
+            .iter()
+            .map(|d| (d.severity, d.message.starts_with("node 1 ")))
+            .collect();
+        assert_eq!(items, vec![(Severity::Warning, true)]);
+    }
+
+    #[test]
+    fn rejects_a_fingerprint_no_rule_records() {
+        let ops = spec(json!({
+            "nodes": [
+                { "id": 0, "block": "stratum 0", "fingerprint": "x" },
+                { "id": 1, "block": "stratum 0", "fingerprint": "y" },
+            ],
+            "rules": [{ "text": "a(x) :- e(x).", "plan_tree": [{ "fingerprint": "x" }] }],
+        }));
+        let error = ops
+            .validate_and_build("ops.json", &mut Diagnostics::new(false))
+            .unwrap_err();
+        assert!(error.to_string().contains("node 1 has fingerprint 'y'"));
+    }
+}
@@ -0,0 +1,490 @@
+//! Layout of the JSON payload embedded in the HTML report.
+//!
+//! Every `ReportData` snapshot repeats the same structure (roots, node labels, tags,
+//! edges, operator addrs, rules). The payload stores that topology once and, per
+//! snapshot, only the metrics, as arrays indexed by node and operator position in
+//! the topology. Stats are written as `[mean, var, min, max]`, and the template
+//! rebuilds `ReportData`-shaped objects from this ("hydration"). Per-worker series
+//! are kept only where the page shows them (the node worker chart and heatmap):
+//! per node, for `total_active_ms` and `batched_in`, rounded to 3 decimals. Rule
+//! plans and relations are topology too; only their costs are per snapshot.
+
+use crate::diagnostics::Diagnostic;
+use crate::runs::{RunMetrics, RunsView};
+use crate::scope::ScopeView;
+use crate::series::SeriesReport;
+use crate::stats::Stats;
+use crate::view::{
+    BlockView, CriticalPaths, InclusiveCost, NameNodeView, OperatorView, RelationView, ReportData,
+    RuleCost, RulePlanNodeView, SharedPolicy, TotalsView, UnmappedOperatorView, WorkerSeries,
+};
+
+use serde::Serialize;
+use serde::ser::{SerializeTuple, Serializer};
+use std::collections::BTreeMap;
+
+#[derive(Serialize)]
+pub struct Payload<'a> {
+    pub snapshot_labels: &'a [String],
+    pub topology: Topology<'a>,
+    pub snapshots: Vec<SnapshotMetrics<'a>>,
+    /// Per-interval increments for the intervals after the first, which is the first
+    /// snapshot itself (empty with a single snapshot). They carry no per-worker
+    /// series: the page takes the difference of adjacent cumulative snapshots.
+    pub interval_snapshots: Vec<SnapshotMetrics<'a>>,
+    /// Per-node/operator/total values across `snapshot_labels`, for trend charts.
+    pub series: &'a SeriesReport,
+    pub diagnostics: &'a [Diagnostic],
+}
+
+/// Structure shared by all snapshots (they are all built from the same ops.json).
+#[derive(Serialize)]
+pub struct Topology<'a> {
+    pub roots: &'a [String],
+    /// Position `i` here is position `i` in every `SnapshotMetrics::nodes`.
+    pub nodes: Vec<NodeTopology<'a>>,
+    /// Position `i` here is position `i` in every `SnapshotMetrics::operators`.
+    pub operators: Vec<OperatorTopology<'a>>,
+    /// Position `i` here is position `i` in every `SnapshotMetrics::blocks`.
+    pub blocks: Vec<BlockTopology<'a>>,
+    /// Position `i` here is position `i` in every `SnapshotMetrics::rules`.
+    pub rules: Vec<RuleTopology<'a>>,
+    /// Position `i` here is position `i` in every `SnapshotMetrics::relations`.
+    pub relations: Vec<RelationTopology<'a>>,
+}
+
+#[derive(Serialize)]
+pub struct RuleTopology<'a> {
+    pub text: &'a str,
+    pub root: &'a str,
+    pub nodes: &'a BTreeMap<String, RulePlanNodeView>,
+}
+
+#[derive(Serialize)]
+pub struct RelationTopology<'a> {
+    pub relation: &'a str,
+    pub signature: &'a str,
+    pub rules: &'a [usize],
+    pub depends_on: &'a [String],
+    pub negated: &'a [String],
+    pub inputs: &'a [String],
+    pub recursive: bool,
+}
+
+#[derive(Serialize)]
+pub struct NodeTopology<'a> {
+    pub name: &'a str,
+    pub label: &'a str,
+    pub block: &'a str,
+    pub fingerprint: Option<&'a str>,
+    pub tags: &'a [String],
+    pub children: &'a [String],
+    pub dag_parents: &'a [String],
+    pub extra_parents: &'a [String],
+    /// Operator positions owned by this node (sorted by addr).
+    pub operators: Vec<usize>,
+}
+
+#[derive(Serialize)]
+pub struct OperatorTopology<'a> {
+    pub addr: &'a [u32],
+    pub op_name: &'a str,
+}
+
+#[derive(Serialize)]
+pub struct BlockTopology<'a> {
+    pub block: &'a str,
+    pub names: &'a [String],
+}
+
+/// Metrics of one snapshot, indexed like the `Topology` arrays.
+#[derive(Serialize)]
+pub struct SnapshotMetrics<'a> {
+    pub num_workers: usize,
+    pub workers: &'a [u32],
+    pub has_time: bool,
+    pub has_memory: bool,
+    pub extra_metrics: &'a [String],
+    pub totals: &'a TotalsView,
+    pub critical_paths: &'a CriticalPaths,
+    pub shared_policy: SharedPolicy,
+    pub nodes: Vec<NodeMetrics<'a>>,
+    pub operators: Vec<OperatorMetrics<'a>>,
+    pub blocks: Vec<BlockMetrics<'a>>,
+    pub rules: Vec<RuleMetrics<'a>>,
+    pub relations: Vec<RuleMetrics<'a>>,
+    pub unmapped: &'a [UnmappedOperatorView],
+    pub scopes: &'a [ScopeView],
+    pub runs: Option<RunsMetrics<'a>>,
+}
+
+/// Run-to-run stats, indexed like the `Topology` arrays.
+#[derive(Serialize)]
+pub struct RunsMetrics<'a> {
+    pub runs: usize,
+    pub totals: RunCompact<'a>,
+    pub nodes: Vec<Option<RunCompact<'a>>>,
+}
+
+#[derive(Serialize)]
+pub struct RunCompact<'a> {
+    pub activations: Compact<'a>,
+    pub total_active_ms: Compact<'a>,
+    pub batched_in: Compact<'a>,
+}
+
+#[derive(Serialize)]
+pub struct NodeMetrics<'a> {
+    pub self_activations: Compact<'a>,
+    pub self_total_active_ms: Compact<'a>,
+    pub self_batched_in: Compact<'a>,
+    pub self_merges: Compact<'a>,
+    pub self_merge_in: Compact<'a>,
+    pub self_merge_out: Compact<'a>,
+    pub self_dropped: Compact<'a>,
+    pub has_memory_data: bool,
+    pub num_workers: usize,
+    pub downstream: InclusiveMetrics<'a>,
+    pub upstream: InclusiveMetrics<'a>,
+    pub subtree: InclusiveMetrics<'a>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workers: Option<NodeWorkers>,
+}
+
+/// The per-worker series the page uses, indexed like `SnapshotMetrics::workers`.
+#[derive(Serialize)]
+pub struct NodeWorkers {
+    pub total_active_ms: Vec<f64>,
+    pub batched_in: Vec<f64>,
+}
+
+#[derive(Serialize)]
+pub struct InclusiveMetrics<'a> {
+    pub nodes: usize,
+    pub activations: Compact<'a>,
+    pub total_active_ms: Compact<'a>,
+    pub batched_in: Compact<'a>,
+}
+
+#[derive(Serialize)]
+pub struct OperatorMetrics<'a> {
+    pub activations: Compact<'a>,
+    pub total_active_ms: Compact<'a>,
+    pub batched_in: Option<Compact<'a>>,
+    pub merges: Option<Compact<'a>>,
+    pub merge_in: Option<Compact<'a>>,
+    pub merge_out: Option<Compact<'a>>,
+    pub dropped: Option<Compact<'a>>,
+    pub extra: BTreeMap<&'a str, Compact<'a>>,
+}
+
+#[derive(Serialize)]
+pub struct BlockMetrics<'a> {
+    pub activations: Compact<'a>,
+    pub total_active_ms: Compact<'a>,
+    pub batched_in: Compact<'a>,
+    pub merges: Compact<'a>,
+    pub merge_in: Compact<'a>,
+    pub merge_out: Compact<'a>,
+    pub dropped: Compact<'a>,
+}
+
+#[derive(Serialize)]
+pub struct RuleMetrics<'a> {
+    pub nodes: usize,
+    pub shared_nodes: usize,
+    pub activations: Compact<'a>,
+    pub total_active_ms: Compact<'a>,
+    pub batched_in: Compact<'a>,
+}
+
+/// `Stats` serialized as `[mean, var, min, max]`.
+pub struct Compact<'a>(&'a Stats);
+
+impl Serialize for Compact<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut t = serializer.serialize_tuple(4)?;
+        t.serialize_element(&self.0.mean)?;
+        t.serialize_element(&self.0.var)?;
+        t.serialize_element(&self.0.min)?;
+        t.serialize_element(&self.0.max)?;
+        t.end()
+    }
+}
+
+impl<'a> Payload<'a> {
+    /// Split snapshots into shared topology + per-snapshot metrics. All snapshots
+    /// must come from `view::build_report_data` over the same ops.json.
+    pub fn new(
+        labels: &'a [String],
+        snapshots: &'a [ReportData],
+        interval_snapshots: &'a [ReportData],
+        series: &'a SeriesReport,
+        diagnostics: &'a [Diagnostic],
+    ) -> Self {
+        Self {
+            snapshot_labels: labels,
+            topology: Topology::new(snapshots),
+            snapshots: snapshots
+                .iter()
+                .map(|s| SnapshotMetrics::new(s, true))
+                .collect(),
+            interval_snapshots: interval_snapshots
+                .iter()
+                .skip(1)
+                .map(|s| SnapshotMetrics::new(s, false))
+                .collect(),
+            series,
+            diagnostics,
+        }
+    }
+}
+
+impl<'a> Topology<'a> {
+    fn new(snapshots: &'a [ReportData]) -> Self {
+        let Some(first) = snapshots.first() else {
+            return Self {
+                roots: &[],
+                nodes: Vec::new(),
+                operators: Vec::new(),
+                blocks: Vec::new(),
+                rules: Vec::new(),
+                relations: Vec::new(),
+            };
+        };
+
+        let mut nodes = Vec::with_capacity(first.nodes.len());
+        let mut operators = Vec::new();
+        for (name, node) in &first.nodes {
+            let start = operators.len();
+            for (i, op) in node.operators.iter().enumerate() {
+                operators.push(OperatorTopology {
+                    addr: &op.addr,
+                    op_name: op_name(snapshots, name, i, op),
+                });
+            }
+            nodes.push(NodeTopology {
+                name,
+                label: &node.label,
+                block: &node.block,
+                fingerprint: node.fingerprint.as_deref(),
+                tags: &node.tags,
+                children: &node.children,
+                dag_parents: &node.dag_parents,
+                extra_parents: &node.extra_parents,
+                operators: (start..operators.len()).collect(),
+            });
+        }
+
+        Self {
+            roots: &first.roots,
+            nodes,
+            operators,
+            blocks: first
+                .blocks
+                .values()
+                .map(|b| BlockTopology {
+                    block: &b.block,
+                    names: &b.names,
+                })
+                .collect(),
+            rules: first
+                .rules
+                .iter()
+                .map(|r| RuleTopology {
+                    text: &r.text,
+                    root: &r.root,
+                    nodes: &r.nodes,
+                })
+                .collect(),
+            relations: first.relations.iter().map(RelationTopology::new).collect(),
+        }
+    }
+}
+
+impl<'a> RelationTopology<'a> {
+    fn new(r: &'a RelationView) -> Self {
+        Self {
+            relation: &r.relation,
+            signature: &r.signature,
+            rules: &r.rules,
+            depends_on: &r.depends_on,
+            negated: &r.negated,
+            inputs: &r.inputs,
+            recursive: r.recursive,
+        }
+    }
+}
+
+/// First non-empty op_name of an operator over all snapshots (it is empty in a
+/// snapshot whose logs lack the operator).
+fn op_name<'a>(snapshots: &'a [ReportData], node: &str, i: usize, op: &'a OperatorView) -> &'a str {
+    if !op.op_name.is_empty() {
+        return &op.op_name;
+    }
+    snapshots
+        .iter()
+        .filter_map(|s| s.nodes.get(node)?.operators.get(i))
+        .map(|o| o.op_name.as_str())
+        .find(|n| !n.is_empty())
+        .unwrap_or_default()
+}
+
+impl<'a> SnapshotMetrics<'a> {
+    /// `with_workers`: include the nodes' per-worker series.
+    fn new(data: &'a ReportData, with_workers: bool) -> Self {
+        Self {
+            num_workers: data.num_workers,
+            workers: &data.workers,
+            has_time: data.has_time,
+            has_memory: data.has_memory,
+            extra_metrics: &data.extra_metrics,
+            totals: &data.totals,
+            critical_paths: &data.critical_paths,
+            shared_policy: data.shared_policy,
+            nodes: data
+                .nodes
+                .values()
+                .map(|n| NodeMetrics::new(n, with_workers))
+                .collect(),
+            operators: data
+                .nodes
+                .values()
+                .flat_map(|n| &n.operators)
+                .map(OperatorMetrics::new)
+                .collect(),
+            blocks: data.blocks.values().map(BlockMetrics::new).collect(),
+            rules: data
+                .rules
+                .iter()
+                .map(|r| RuleMetrics::new(&r.cost))
+                .collect(),
+            relations: data
+                .relations
+                .iter()
+                .map(|r| RuleMetrics::new(&r.cost))
+                .collect(),
+            unmapped: &data.unmapped,
+            scopes: &data.scopes,
+            runs: data.runs.as_ref().map(|r| RunsMetrics::new(r, data)),
+        }
+    }
+}
+
+impl<'a> RunsMetrics<'a> {
+    fn new(r: &'a RunsView, data: &ReportData) -> Self {
+        Self {
+            runs: r.runs,
+            totals: RunCompact::new(&r.totals),
+            nodes: data
+                .nodes
+                .keys()
+                .map(|n| r.nodes.get(n).map(RunCompact::new))
+                .collect(),
+        }
+    }
+}
+
+impl<'a> RunCompact<'a> {
+    fn new(m: &'a RunMetrics) -> Self {
+        Self {
+            activations: Compact(&m.activations),
+            total_active_ms: Compact(&m.total_active_ms),
+            batched_in: Compact(&m.batched_in),
+        }
+    }
+}
+
+impl<'a> NodeMetrics<'a> {
+    fn new(n: &'a NameNodeView, with_workers: bool) -> Self {
+        Self {
+            self_activations: Compact(&n.self_activations),
+            self_total_active_ms: Compact(&n.self_total_active_ms),
+            self_batched_in: Compact(&n.self_batched_in),
+            self_merges: Compact(&n.self_merges),
+            self_merge_in: Compact(&n.self_merge_in),
+            self_merge_out: Compact(&n.self_merge_out),
+            self_dropped: Compact(&n.self_dropped),
+            has_memory_data: n.has_memory_data,
+            num_workers: n.num_workers,
+            downstream: InclusiveMetrics::new(&n.downstream),
+            upstream: InclusiveMetrics::new(&n.upstream),
+            subtree: InclusiveMetrics::new(&n.subtree),
+            workers: with_workers.then(|| NodeWorkers::new(&n.workers)),
+        }
+    }
+}
+
+impl NodeWorkers {
+    fn new(w: &WorkerSeries) -> Self {
+        Self {
+            total_active_ms: quantize(&w.total_active_ms),
+            batched_in: quantize(&w.batched_in),
+        }
+    }
+}
+
+/// Round to 3 decimals (µs for times): finer than the page displays, and much
+/// shorter in JSON than full `f64` precision.
+fn quantize(values: &[f64]) -> Vec<f64> {
+    values
+        .iter()
+        .map(|v| (v * 1000.0).round() / 1000.0)
+        .collect()
+}
+
+impl<'a> InclusiveMetrics<'a> {
+    fn new(c: &'a InclusiveCost) -> Self {
+        Self {
+            nodes: c.nodes,
+            activations: Compact(&c.activations),
+            total_active_ms: Compact(&c.total_active_ms),
+            batched_in: Compact(&c.batched_in),
+        }
+    }
+}
+
+impl<'a> OperatorMetrics<'a> {
+    fn new(op: &'a OperatorView) -> Self {
+        Self {
+            activations: Compact(&op.activations),
+            total_active_ms: Compact(&op.total_active_ms),
+            batched_in: op.batched_in.as_ref().map(Compact),
+            merges: op.merges.as_ref().map(Compact),
+            merge_in: op.merge_in.as_ref().map(Compact),
+            merge_out: op.merge_out.as_ref().map(Compact),
+            dropped: op.dropped.as_ref().map(Compact),
+            extra: op
+                .extra
+                .iter()
+                .map(|(k, v)| (k.as_str(), Compact(v)))
+                .collect(),
+        }
+    }
+}
+
+impl<'a> BlockMetrics<'a> {
+    fn new(b: &'a BlockView) -> Self {
+        Self {
+            activations: Compact(&b.activations),
+            total_active_ms: Compact(&b.total_active_ms),
+            batched_in: Compact(&b.batched_in),
+            merges: Compact(&b.merges),
+            merge_in: Compact(&b.merge_in),
+            merge_out: Compact(&b.merge_out),
+            dropped: Compact(&b.dropped),
+        }
+    }
+}
+
+impl<'a> RuleMetrics<'a> {
+    fn new(c: &'a RuleCost) -> Self {
+        Self {
+            nodes: c.nodes,
+            shared_nodes: c.shared_nodes,
+            activations: Compact(&c.activations),
+            total_active_ms: Compact(&c.total_active_ms),
+            batched_in: Compact(&c.batched_in),
+        }
+    }
+}
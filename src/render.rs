@@ -1,25 +1,103 @@
 //! Report rendering (HTML).
 
 use crate::Result;
+use crate::diagnostics::Diagnostic;
+use crate::diff::DiffReport;
+use crate::payload::Payload;
+use crate::series::SeriesReport;
 use crate::view::ReportData;
 
-use serde::Serialize;
+use anyhow::anyhow;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use flate2::Compression;
+use flate2::write::GzEncoder;
 use serde_json::to_string;
+use std::io::Write;
 
 const TEMPLATE: &str = include_str!("../templates/report.html");
+const DIFF_TEMPLATE: &str = include_str!("../templates/diff.html");
 
-#[derive(Serialize)]
-struct ReportWrapper<'a> {
-    snapshot_labels: &'a [String],
-    snapshots: &'a [ReportData],
+/// Render a self-contained HTML report (data embedded as JSON, see `payload`).
+///
+/// With `compress`, the JSON is gzipped and embedded base64-encoded as
+/// `{"gzip_base64": "..."}`; the template inflates it with `DecompressionStream`.
+pub fn render_html_report(
+    labels: &[String],
+    snapshots: &[ReportData],
+    interval_snapshots: &[ReportData],
+    series: &SeriesReport,
+    diagnostics: &[Diagnostic],
+    compress: bool,
+) -> Result<String> {
+    let payload = Payload::new(labels, snapshots, interval_snapshots, series, diagnostics);
+    let json = to_string(&payload)?;
+    let data = if compress {
+        format!(
+            "{{\"gzip_base64\":\"{}\"}}",
+            STANDARD.encode(gzip(json.as_bytes())?)
+        )
+    } else {
+        json
+    };
+    Ok(TEMPLATE.replace("__DATA__", &data))
 }
 
-/// Render a self-contained HTML report (data embedded as JSON).
-pub fn render_html_report(labels: &[String], snapshots: &[ReportData]) -> Result<String> {
-    let wrapper = ReportWrapper {
-        snapshot_labels: labels,
-        snapshots,
-    };
-    let json = to_string(&wrapper)?;
-    Ok(TEMPLATE.replace("__DATA__", &json))
+/// Render a self-contained HTML diff report of two runs.
+pub fn render_diff_report(report: &DiffReport, diagnostics: &[Diagnostic]) -> Result<String> {
+    fill(
+        DIFF_TEMPLATE,
+        &[
+            ("__DATA__", &to_string(report)?),
+            ("__DIAGNOSTICS__", &to_string(diagnostics)?),
+        ],
+    )
+}
+
+/// Substitute each placeholder of `template` once, in template order, so that a
+/// value containing a later placeholder (e.g. in a rule text) is left as is.
+fn fill(template: &str, values: &[(&str, &str)]) -> Result<String> {
+    let mut out =
+        String::with_capacity(template.len() + values.iter().map(|(_, v)| v.len()).sum::<usize>());
+    let mut rest = template;
+    for (placeholder, value) in values {
+        let (head, tail) = rest
+            .split_once(placeholder)
+            .ok_or_else(|| anyhow!("template has no {} placeholder", placeholder))?;
+        out.push_str(head);
+        out.push_str(value);
+        rest = tail;
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn gzip(bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(bytes)?;
+    Ok(encoder.finish()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn fill_does_not_substitute_inside_values() {
+        let filled = fill(
+            "a=__DATA__; b=__DIAGNOSTICS__;",
+            &[
+                ("__DATA__", "\"__DIAGNOSTICS__\""),
+                ("__DIAGNOSTICS__", "[]"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(filled, "a=\"__DIAGNOSTICS__\"; b=[];");
+    }
+
+    #[test]
+    fn fill_reports_a_missing_placeholder() {
+        assert!(fill("no placeholders", &[("__DATA__", "{}")]).is_err());
+    }
 }
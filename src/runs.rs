@@ -0,0 +1,394 @@
+//! Repeated runs of one program, merged into one averaged profile.
+//!
+//! Runs are a separate dimension from workers. The averaged snapshot holds, per
+//! operator and worker, the mean of that worker's value over the runs, so the report
+//! keeps the worker skew seen within a run. Run-to-run variation is summarized on its
+//! own (`RunsView`): stats over runs of each run's mean-over-workers value.
+
+use crate::Result;
+use crate::addr::Addr;
+use crate::diagnostics::{Diagnostics, Location};
+use crate::log::{MemoryIndex, MemoryRow, Snapshot, TimeIndex, TimeRow};
+use crate::ops::ValidatedOps;
+use crate::stats::Stats;
+use crate::view::ReportData;
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Stats over runs (not workers) of a metric's per-run mean. The variance is the
+/// unbiased sample variance (divided by `runs - 1`): a few runs are a sample of
+/// the run-to-run variation.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RunMetrics {
+    pub activations: Stats,
+    pub total_active_ms: Stats,
+    pub batched_in: Stats,
+}
+
+/// Run-to-run variation of one snapshot label.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunsView {
+    /// Number of runs with this snapshot.
+    pub runs: usize,
+    /// Mapped totals.
+    pub totals: RunMetrics,
+    pub blocks: BTreeMap<String, RunMetrics>,
+    pub nodes: BTreeMap<String, RunMetrics>,
+}
+
+/// Check that a further run's ops.json describes the same program as the first
+/// one: the same nodes, with the same labels, blocks, fingerprints and operators.
+pub fn check_same_program(
+    first: &ValidatedOps,
+    other: &ValidatedOps,
+    other_path: &str,
+    diags: &mut Diagnostics,
+) -> Result<()> {
+    let first_ids: BTreeSet<&u32> = first.nodes.keys().collect();
+    let other_ids: BTreeSet<&u32> = other.nodes.keys().collect();
+    if first_ids != other_ids {
+        let differing = first_ids.symmetric_difference(&other_ids).count();
+        return diags.error(
+            Some(Location::file(other_path)),
+            format!(
+                "does not describe the same program as the first run: {} node id(s) are in only one of them",
+                differing
+            ),
+        );
+    }
+    for (id, a) in &first.nodes {
+        let b = &other.nodes[id];
+        if a.label != b.label
+            || a.block != b.block
+            || a.fingerprint != b.fingerprint
+            || a.operators != b.operators
+        {
+            return diags.error(
+                Some(Location::file(other_path)),
+                format!(
+                    "does not describe the same program as the first run: node {} ({}) differs in label, block, fingerprint or operators",
+                    id, a.label
+                ),
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Averaged time snapshots, one per label found in every run.
+pub fn average_time(
+    runs: &[Vec<Snapshot<TimeIndex>>],
+    diags: &mut Diagnostics,
+) -> Result<Vec<Snapshot<TimeIndex>>> {
+    average(
+        runs,
+        "time",
+        diags,
+        |r| &r.op_name,
+        |rows, workers| {
+            let activations = mean_by_worker(rows, workers, |r| &r.activations_by_worker);
+            let ms = mean_by_worker(rows, workers, |r| &r.total_active_ms_by_worker);
+            TimeRow {
+                activations: Stats::from_values(&activations),
+                total_active_ms: Stats::from_values(&ms),
+                activations_by_worker: activations,
+                total_active_ms_by_worker: ms,
+                num_workers: workers.len(),
+                ..rows[0].1.clone()
+            }
+        },
+    )
+}
+
+/// Averaged memory snapshots, one per label found in every run.
+pub fn average_memory(
+    runs: &[Vec<Snapshot<MemoryIndex>>],
+    diags: &mut Diagnostics,
+) -> Result<Vec<Snapshot<MemoryIndex>>> {
+    average(
+        runs,
+        "memory",
+        diags,
+        |r| &r.op_name,
+        |rows, workers| {
+            let m = |get: fn(&MemoryRow) -> &[f64]| mean_by_worker(rows, workers, get);
+            let batched_in = m(|r| &r.batched_in_by_worker);
+            let merges = m(|r| &r.merges_by_worker);
+            let merge_in = m(|r| &r.merge_in_by_worker);
+            let merge_out = m(|r| &r.merge_out_by_worker);
+            let dropped = m(|r| &r.dropped_by_worker);
+            MemoryRow {
+                batched_in: Stats::from_values(&batched_in),
+                merges: Stats::from_values(&merges),
+                merge_in: Stats::from_values(&merge_in),
+                merge_out: Stats::from_values(&merge_out),
+                dropped: Stats::from_values(&dropped),
+                batched_in_by_worker: batched_in,
+                merges_by_worker: merges,
+                merge_in_by_worker: merge_in,
+                merge_out_by_worker: merge_out,
+                dropped_by_worker: dropped,
+                num_workers: workers.len(),
+                ..rows[0].1.clone()
+            }
+        },
+    )
+}
+
+/// A log row of one run, with that run's worker ids.
+type RunRow<'a, R> = (&'a [u32], &'a R);
+
+/// Average the runs label by label with `row_mean`, which gets the rows of an
+/// operator in every run that logged it and the union of the runs' workers.
+///
+/// Labels missing from some run, differing worker sets and operators missing from
+/// some run are warnings (an operator is averaged over the runs that have it); an
+/// address with different op_names in two runs is an error. Extra (header-mapped)
+/// columns are taken from the first run that has the operator.
+fn average<R>(
+    runs: &[Vec<Snapshot<BTreeMap<Addr, R>>>],
+    kind: &str,
+    diags: &mut Diagnostics,
+    op_name: fn(&R) -> &String,
+    row_mean: impl Fn(&[RunRow<'_, R>], &[u32]) -> R,
+) -> Result<Vec<Snapshot<BTreeMap<Addr, R>>>> {
+    let mut labels: Vec<&String> = Vec::new();
+    for label in runs.iter().flatten().map(|s| &s.label) {
+        if !labels.contains(&label) {
+            labels.push(label);
+        }
+    }
+    let mut out = Vec::new();
+    for label in labels {
+        let snapshots: Vec<&Snapshot<BTreeMap<Addr, R>>> = runs
+            .iter()
+            .filter_map(|run| run.iter().find(|s| &s.label == label))
+            .collect();
+        if snapshots.len() < runs.len() {
+            diags.warning(
+                None,
+                format!(
+                    "{} snapshot {} is missing from {} of {} runs; it is left out",
+                    kind,
+                    label,
+                    runs.len() - snapshots.len(),
+                    runs.len()
+                ),
+            );
+            continue;
+        }
+
+        let workers: BTreeSet<u32> = snapshots
+            .iter()
+            .flat_map(|s| s.workers.iter().copied())
+            .collect();
+        let workers: Vec<u32> = workers.into_iter().collect();
+        if snapshots.iter().any(|s| s.workers != workers) {
+            diags.warning(
+                None,
+                format!(
+                    "{} snapshot {}: the runs have different worker sets; each worker is averaged over the runs that have it",
+                    kind, label
+                ),
+            );
+        }
+
+        let addrs: BTreeSet<&Addr> = snapshots.iter().flat_map(|s| s.data.keys()).collect();
+        let mut partial = 0usize;
+        let mut data = BTreeMap::new();
+        for addr in addrs {
+            let rows: Vec<RunRow<'_, R>> = snapshots
+                .iter()
+                .filter_map(|s| Some((s.workers.as_slice(), s.data.get(addr)?)))
+                .collect();
+            if rows.len() < snapshots.len() {
+                partial += 1;
+            }
+            if let Some((_, other)) = rows.iter().find(|(_, r)| op_name(r) != op_name(rows[0].1)) {
+                diags.error(
+                    None,
+                    format!(
+                        "{} snapshot {}: addr {:?} is `{}` in one run and `{}` in another; the runs are not of the same program",
+                        kind,
+                        label,
+                        addr.0,
+                        op_name(rows[0].1),
+                        op_name(other)
+                    ),
+                )?;
+            }
+            data.insert(addr.clone(), row_mean(&rows, &workers));
+        }
+        if partial > 0 {
+            diags.warning(
+                None,
+                format!(
+                    "{} snapshot {}: {} operator(s) are missing from some runs; they are averaged over the runs that have them",
+                    kind, label, partial
+                ),
+            );
+        }
+
+        out.push(Snapshot {
+            label: label.clone(),
+            workers,
+            data,
+        });
+    }
+    Ok(out)
+}
+
+/// Per worker of `workers`, the mean of `get` over the rows whose run has that worker.
+fn mean_by_worker<R>(rows: &[RunRow<'_, R>], workers: &[u32], get: fn(&R) -> &[f64]) -> Vec<f64> {
+    workers
+        .iter()
+        .map(|worker| {
+            let values: Vec<f64> = rows
+                .iter()
+                .filter_map(|(run_workers, row)| {
+                    let pos = run_workers.binary_search(worker).ok()?;
+                    get(row).get(pos).copied()
+                })
+                .collect();
+            if values.is_empty() {
+                0.0
+            } else {
+                values.iter().sum::<f64>() / values.len() as f64
+            }
+        })
+        .collect()
+}
+
+/// Activations, total_active_ms and batched_in of something in one run.
+type RunStats<'a> = (&'a Stats, &'a Stats, &'a Stats);
+
+/// Run-to-run stats of one snapshot label, from each run's report data.
+pub fn build_runs_view(runs: &[&ReportData]) -> RunsView {
+    let over_runs = |get: &dyn Fn(&ReportData) -> Option<RunStats<'_>>| {
+        let (mut activations, mut ms, mut batched_in) = (Vec::new(), Vec::new(), Vec::new());
+        for (a, t, b) in runs.iter().filter_map(|r| get(r)) {
+            activations.push(a.mean);
+            ms.push(t.mean);
+            batched_in.push(b.mean);
+        }
+        RunMetrics {
+            activations: Stats::from_sample(&activations),
+            total_active_ms: Stats::from_sample(&ms),
+            batched_in: Stats::from_sample(&batched_in),
+        }
+    };
+
+    let totals = over_runs(&|r| {
+        let t = &r.totals;
+        Some((
+            &t.total_mapped_activations,
+            &t.total_mapped_ms,
+            &t.total_batched_in,
+        ))
+    });
+    let blocks = runs
+        .iter()
+        .flat_map(|r| r.blocks.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(|block| {
+            let metrics = over_runs(&|r| {
+                let b = r.blocks.get(block)?;
+                Some((&b.activations, &b.total_active_ms, &b.batched_in))
+            });
+            (block.clone(), metrics)
+        })
+        .collect();
+    let nodes = runs
+        .iter()
+        .flat_map(|r| r.nodes.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(|name| {
+            let metrics = over_runs(&|r| {
+                let n = r.nodes.get(name)?;
+                Some((
+                    &n.self_activations,
+                    &n.self_total_active_ms,
+                    &n.self_batched_in,
+                ))
+            });
+            (name.clone(), metrics)
+        })
+        .collect();
+
+    RunsView {
+        runs: runs.len(),
+        totals,
+        blocks,
+        nodes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ops::OpsSpec;
+    use crate::view::{self, Program, SharedPolicy};
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    /// One run's time snapshot `t0` of workers 0 and 1, with one operator at `[0, 1]`.
+    fn run(ms: [f64; 2]) -> Vec<Snapshot<TimeIndex>> {
+        let row = TimeRow {
+            activations: Stats::from_values(&[1.0, 1.0]),
+            total_active_ms: Stats::from_values(&ms),
+            activations_by_worker: vec![1.0, 1.0],
+            total_active_ms_by_worker: ms.to_vec(),
+            extra: BTreeMap::new(),
+            op_name: "Map".to_string(),
+            num_workers: 2,
+        };
+        vec![Snapshot {
+            label: "t0".to_string(),
+            workers: vec![0, 1],
+            data: BTreeMap::from([(Addr::new(vec![0, 1]), row)]),
+        }]
+    }
+
+    #[test]
+    fn two_runs_average_per_worker_with_unbiased_run_variance() {
+        let runs = vec![run([2.0, 4.0]), run([4.0, 8.0])];
+        let mut diags = Diagnostics::new(false);
+
+        let averaged = average_time(&runs, &mut diags).unwrap();
+        assert_eq!(averaged.len(), 1);
+        let row = &averaged[0].data[&Addr::new(vec![0, 1])];
+        assert_eq!(row.total_active_ms_by_worker, vec![3.0, 6.0]);
+        assert_eq!(row.total_active_ms.mean, 4.5);
+
+        // Run means are 3 and 6 ms: over two runs, the variance is (6 - 3)^2 / 2.
+        let spec: OpsSpec = serde_json::from_value(json!({
+            "nodes": [{ "id": 0, "block": "stratum 0", "operators": [[0, 1]] }],
+        }))
+        .unwrap();
+        let ops = spec.validate_and_build("ops.json", &mut diags).unwrap();
+        let program = Program::new(ops, &mut diags);
+        let reports: Vec<ReportData> = runs
+            .iter()
+            .map(|run| {
+                view::build_report_data(
+                    &program,
+                    Some(&run[0]),
+                    None,
+                    SharedPolicy::Split,
+                    &mut diags,
+                )
+                .unwrap()
+            })
+            .collect();
+        let view = build_runs_view(&reports.iter().collect::<Vec<_>>());
+
+        assert_eq!(view.runs, 2);
+        let ms = &view.totals.total_active_ms;
+        assert_eq!((ms.mean, ms.var, ms.min, ms.max), (4.5, 4.5, 3.0, 6.0));
+        assert_eq!(view.nodes["0"].total_active_ms.var, 4.5);
+        assert!(diags.items().is_empty());
+    }
+}
@@ -0,0 +1,193 @@
+//! Physical view: Timely scopes rolled up from operator addresses.
+//!
+//! An address like `[0, 877, 12]` is operator 12 inside scope `[0, 877]` (an
+//! iterative region) inside dataflow `[0]`. Every proper prefix of a logged address
+//! is a scope; the other logged addresses are leaf operators. A scope's rollup sums
+//! its leaf operators per worker, so nested scopes are not counted twice (a scope's
+//! own log row already includes its children's time).
+
+use crate::addr::Addr;
+use crate::log::{MemoryIndex, TimeIndex};
+use crate::stats::{self, Stats};
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Leaf operators owned by no node are grouped under this block name.
+pub const UNMAPPED_BLOCK: &str = "(unmapped)";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScopeView {
+    pub addr: Vec<u32>,
+    /// Name of the scope operator's own log row (e.g. `Iterative`); empty when the
+    /// scope has no row.
+    pub op_name: String,
+    /// Leaf operators anywhere inside the scope.
+    pub operators: usize,
+    /// The scope operator's own logged time (children included, plus scheduling).
+    pub scope_ms: Option<Stats>,
+    /// Per-worker sums over the leaf operators inside the scope.
+    pub activations: Stats,
+    pub total_active_ms: Stats,
+    pub batched_in: Stats,
+    /// Mean leaf time (ms) by the block of the node owning each leaf, to compare the
+    /// physical nesting with the logical strata.
+    pub blocks: BTreeMap<String, f64>,
+}
+
+#[derive(Default)]
+struct Rollup {
+    operators: usize,
+    activations: Vec<f64>,
+    total_active_ms: Vec<f64>,
+    batched_in: Vec<f64>,
+    blocks: BTreeMap<String, f64>,
+}
+
+/// Scopes of one snapshot, sorted by address (a scope comes right before its
+/// contents). `align_time` and `align_memory` map a log row's per-worker values onto
+/// the report's workers; `block_of` gives the block of the node owning an address.
+pub fn build_scopes<'a>(
+    time: &TimeIndex,
+    memory: &MemoryIndex,
+    align_time: impl Fn(&[f64]) -> Vec<f64>,
+    align_memory: impl Fn(&[f64]) -> Vec<f64>,
+    block_of: impl Fn(&Addr) -> Option<&'a str>,
+) -> Vec<ScopeView> {
+    let logged: BTreeSet<&Addr> = time.keys().chain(memory.keys()).collect();
+    let scopes: BTreeSet<&[u32]> = logged
+        .iter()
+        .flat_map(|addr| (1..addr.0.len()).map(|len| &addr.0[..len]))
+        .collect();
+
+    // Every scope is a prefix of some leaf, so each one gets a rollup below.
+    let mut rollups: BTreeMap<&[u32], Rollup> = BTreeMap::new();
+    for addr in logged.iter().filter(|a| !scopes.contains(a.0.as_slice())) {
+        let activations = time
+            .get(*addr)
+            .map(|t| align_time(&t.activations_by_worker));
+        let ms = time
+            .get(*addr)
+            .map(|t| align_time(&t.total_active_ms_by_worker));
+        let batched_in = memory
+            .get(*addr)
+            .map(|m| align_memory(&m.batched_in_by_worker));
+        let mean_ms = time.get(*addr).map_or(0.0, |t| t.total_active_ms.mean);
+        let block = block_of(addr).unwrap_or(UNMAPPED_BLOCK);
+
+        for len in 1..addr.0.len() {
+            let r = rollups.entry(&addr.0[..len]).or_default();
+            r.operators += 1;
+            if let Some(v) = &activations {
+                stats::accumulate(&mut r.activations, v);
+            }
+            if let Some(v) = &ms {
+                stats::accumulate(&mut r.total_active_ms, v);
+            }
+            if let Some(v) = &batched_in {
+                stats::accumulate(&mut r.batched_in, v);
+            }
+            *r.blocks.entry(block.to_string()).or_default() += mean_ms;
+        }
+    }
+
+    rollups
+        .into_iter()
+        .map(|(addr, r)| {
+            let key = Addr::new(addr.to_vec());
+            let op_name = time
+                .get(&key)
+                .map(|t| &t.op_name)
+                .or(memory.get(&key).map(|m| &m.op_name))
+                .cloned()
+                .unwrap_or_default();
+            ScopeView {
+                addr: key.0.clone(),
+                op_name,
+                operators: r.operators,
+                scope_ms: time.get(&key).map(|t| t.total_active_ms.clone()),
+                activations: Stats::from_values(&r.activations),
+                total_active_ms: Stats::from_values(&r.total_active_ms),
+                batched_in: Stats::from_values(&r.batched_in),
+                blocks: r.blocks,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::TimeRow;
+    use pretty_assertions::assert_eq;
+
+    /// Time rows of two workers: `(addr, op_name, ms per worker)`.
+    fn time(rows: &[(&[u32], &str, [f64; 2])]) -> TimeIndex {
+        rows.iter()
+            .map(|(addr, op_name, ms)| {
+                let row = TimeRow {
+                    activations: Stats::from_values(&[1.0, 1.0]),
+                    total_active_ms: Stats::from_values(ms),
+                    activations_by_worker: vec![1.0, 1.0],
+                    total_active_ms_by_worker: ms.to_vec(),
+                    extra: BTreeMap::new(),
+                    op_name: op_name.to_string(),
+                    num_workers: 2,
+                };
+                (Addr::new(addr.to_vec()), row)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn nested_scopes_roll_up_their_leaf_operators() {
+        let time = time(&[
+            (&[0], "Dataflow", [20.0, 20.0]),
+            (&[0, 1], "Input", [4.0, 4.0]),
+            (&[0, 2], "Iterative", [5.0, 6.0]),
+            (&[0, 2, 1], "Join", [1.0, 2.0]),
+            (&[0, 2, 2], "Feedback", [3.0, 3.0]),
+        ]);
+        let mapped = Addr::new(vec![0, 2, 1]);
+        let scopes = build_scopes(
+            &time,
+            &MemoryIndex::new(),
+            |v| v.to_vec(),
+            |v| v.to_vec(),
+            |addr| (*addr == mapped).then_some("stratum 0"),
+        );
+
+        let rollup: Vec<_> = scopes
+            .iter()
+            .map(|s| {
+                (
+                    s.addr.clone(),
+                    s.op_name.as_str(),
+                    s.operators,
+                    s.total_active_ms.mean,
+                    s.scope_ms.as_ref().map(|t| t.mean),
+                )
+            })
+            .collect();
+        // The scope rows themselves are not leaves: [0] sums [0, 1], [0, 2, 1] and
+        // [0, 2, 2] only.
+        assert_eq!(
+            rollup,
+            vec![
+                (vec![0], "Dataflow", 3, 8.5, Some(20.0)),
+                (vec![0, 2], "Iterative", 2, 4.5, Some(5.5)),
+            ]
+        );
+        assert_eq!(
+            scopes[1].blocks,
+            BTreeMap::from([
+                ("stratum 0".to_string(), 1.5),
+                (UNMAPPED_BLOCK.to_string(), 3.0),
+            ])
+        );
+        assert_eq!(
+            (scopes[1].total_active_ms.min, scopes[1].total_active_ms.max),
+            (4.0, 5.0)
+        );
+    }
+}
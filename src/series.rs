@@ -0,0 +1,221 @@
+//! Cross-snapshot time series: how every node, operator and the totals evolve over
+//! the snapshot labels, with a growth estimate that flags nodes whose cost grows
+//! faster than linearly across incremental epochs.
+
+use crate::view::ReportData;
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Growth exponent above which a node counts as growing faster than linearly.
+const SUPERLINEAR_EXPONENT: f64 = 1.25;
+
+/// Nodes below this share of the final mapped time are never flagged (too noisy).
+const MIN_TIME_SHARE: f64 = 0.01;
+
+/// Mean-over-workers value of each metric at every snapshot (indexed like the
+/// snapshot labels). `None` where the snapshot has no data from that log.
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricSeries {
+    pub total_active_ms: Vec<Option<f64>>,
+    pub activations: Vec<Option<f64>>,
+    pub batched_in: Vec<Option<f64>>,
+}
+
+impl MetricSeries {
+    fn new(len: usize) -> Self {
+        Self {
+            total_active_ms: vec![None; len],
+            activations: vec![None; len],
+            batched_in: vec![None; len],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeSeries {
+    pub series: MetricSeries,
+    /// Exponent `k` of a `cumulative time ~ epoch^k` fit (1 is linear). `None` with
+    /// fewer than three snapshots that have time for this node.
+    pub growth: Option<f64>,
+    pub superlinear: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OperatorSeries {
+    pub addr: Vec<u32>,
+    pub node: String,
+    pub op_name: String,
+    pub series: MetricSeries,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SeriesReport {
+    pub labels: Vec<String>,
+    pub totals: MetricSeries,
+    pub nodes: BTreeMap<String, NodeSeries>,
+    /// Sorted by (node, addr).
+    pub operators: Vec<OperatorSeries>,
+    /// Names of superlinear nodes, fastest-growing first.
+    pub superlinear: Vec<String>,
+}
+
+/// Collect the per-snapshot values of every node, operator and total.
+pub fn build_series(labels: &[String], snapshots: &[ReportData]) -> SeriesReport {
+    let n = snapshots.len();
+    let mut totals = MetricSeries::new(n);
+    let mut nodes: BTreeMap<String, MetricSeries> = BTreeMap::new();
+    let mut operators: BTreeMap<(String, Vec<u32>), OperatorSeries> = BTreeMap::new();
+
+    for (i, data) in snapshots.iter().enumerate() {
+        let (time, memory) = (data.has_time, data.has_memory);
+        let at = |on: bool, v: f64| on.then_some(v);
+
+        totals.total_active_ms[i] = at(time, data.totals.total_mapped_ms.mean);
+        totals.activations[i] = at(time, data.totals.total_mapped_activations.mean);
+        totals.batched_in[i] = at(memory, data.totals.total_batched_in.mean);
+
+        for (name, node) in &data.nodes {
+            let s = nodes
+                .entry(name.clone())
+                .or_insert_with(|| MetricSeries::new(n));
+            s.total_active_ms[i] = at(time, node.self_total_active_ms.mean);
+            s.activations[i] = at(time, node.self_activations.mean);
+            s.batched_in[i] = at(memory, node.self_batched_in.mean);
+
+            for op in &node.operators {
+                let entry = operators
+                    .entry((name.clone(), op.addr.clone()))
+                    .or_insert_with(|| OperatorSeries {
+                        addr: op.addr.clone(),
+                        node: name.clone(),
+                        op_name: op.op_name.clone(),
+                        series: MetricSeries::new(n),
+                    });
+                entry.series.total_active_ms[i] = at(time, op.total_active_ms.mean);
+                entry.series.activations[i] = at(time, op.activations.mean);
+                entry.series.batched_in[i] = op.batched_in.as_ref().map(|s| s.mean);
+            }
+        }
+    }
+
+    let final_total = totals
+        .total_active_ms
+        .iter()
+        .rev()
+        .find_map(|v| *v)
+        .unwrap_or(0.0);
+
+    let nodes: BTreeMap<String, NodeSeries> = nodes
+        .into_iter()
+        .map(|(name, series)| {
+            let growth = growth_exponent(&series.total_active_ms);
+            let last = series
+                .total_active_ms
+                .iter()
+                .rev()
+                .find_map(|v| *v)
+                .unwrap_or(0.0);
+            let significant = final_total > 0.0 && last / final_total >= MIN_TIME_SHARE;
+            let superlinear = significant && growth.is_some_and(|k| k > SUPERLINEAR_EXPONENT);
+            (
+                name,
+                NodeSeries {
+                    series,
+                    growth,
+                    superlinear,
+                },
+            )
+        })
+        .collect();
+
+    let mut superlinear: Vec<(&String, f64)> = nodes
+        .iter()
+        .filter(|(_, s)| s.superlinear)
+        .map(|(name, s)| (name, s.growth.unwrap_or(0.0)))
+        .collect();
+    superlinear.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let superlinear = superlinear
+        .into_iter()
+        .map(|(name, _)| name.clone())
+        .collect();
+
+    SeriesReport {
+        labels: labels.to_vec(),
+        totals,
+        operators: operators.into_values().collect(),
+        nodes,
+        superlinear,
+    }
+}
+
+/// Least-squares slope of `ln(value)` over `ln(epoch)`, with epochs numbered from 1
+/// in snapshot order. Cumulative cost that grows by the same amount every epoch
+/// gives 1; a larger slope means each epoch costs more than the one before.
+fn growth_exponent(cumulative: &[Option<f64>]) -> Option<f64> {
+    let points: Vec<(f64, f64)> = cumulative
+        .iter()
+        .enumerate()
+        .filter_map(|(i, v)| match v {
+            Some(v) if *v > 0.0 => Some((((i + 1) as f64).ln(), v.ln())),
+            _ => None,
+        })
+        .collect();
+    if points.len() < 3 {
+        return None;
+    }
+
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_y = points.iter().map(|p| p.1).sum::<f64>() / n;
+    let sxx: f64 = points.iter().map(|p| (p.0 - mean_x).powi(2)).sum();
+    let sxy: f64 = points.iter().map(|p| (p.0 - mean_x) * (p.1 - mean_y)).sum();
+    (sxx > 0.0).then(|| sxy / sxx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn growth(values: &[f64]) -> Option<f64> {
+        let cumulative: Vec<Option<f64>> = values.iter().map(|v| Some(*v)).collect();
+        growth_exponent(&cumulative)
+    }
+
+    fn assert_close(actual: Option<f64>, expected: f64) {
+        let actual = actual.expect("a growth exponent");
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} is not close to {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn linear_growth_has_exponent_one() {
+        assert_close(growth(&[3.0, 6.0, 9.0, 12.0]), 1.0);
+    }
+
+    #[test]
+    fn quadratic_growth_has_exponent_two() {
+        assert_close(growth(&[1.0, 4.0, 9.0, 16.0, 25.0]), 2.0);
+        // Snapshots without time are skipped, but keep their epoch number.
+        assert_close(
+            growth_exponent(&[Some(1.0), None, Some(9.0), Some(16.0)]),
+            2.0,
+        );
+    }
+
+    #[test]
+    fn too_few_points_have_no_exponent() {
+        assert_eq!(growth(&[]), None);
+        assert_eq!(growth(&[5.0]), None);
+        assert_eq!(growth(&[5.0, 10.0]), None);
+        // Zero readings (a node that never ran) are not points.
+        assert_eq!(growth(&[0.0, 0.0, 0.0]), None);
+        assert_eq!(growth(&[0.0, 0.0, 4.0, 8.0]), None);
+        assert_eq!(growth_exponent(&[None, None, None]), None);
+    }
+}
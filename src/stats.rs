@@ -1,10 +1,9 @@
 //! Shared statistics type used by both log parsing and view aggregation.
 
-use serde::Serialize;
-use std::ops::Add;
+use serde::{Deserialize, Serialize};
 
 /// Per-field statistics across workers: mean, variance, min, max.
-#[derive(Debug, Clone, Serialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Stats {
     pub mean: f64,
     pub var: f64,
@@ -13,10 +12,6 @@ pub struct Stats {
 }
 
 impl Stats {
-    pub fn new(mean: f64, var: f64, min: f64, max: f64) -> Self {
-        Self { mean, var, min, max }
-    }
-
     /// Compute stats from a slice of per-worker values.
     pub fn from_values(values: &[f64]) -> Self {
         let n = values.len() as f64;
@@ -31,20 +26,327 @@ impl Stats {
         } else {
             0.0
         };
-        Self { mean, var, min, max }
+        Self {
+            mean,
+            var,
+            min,
+            max,
+        }
+    }
+
+    /// Like `from_values`, but with the unbiased variance (divided by `n - 1`), for
+    /// a sample such as the per-run means of repeated runs.
+    pub fn from_sample(values: &[f64]) -> Self {
+        let mut stats = Self::from_values(values);
+        let n = values.len();
+        if n > 1 {
+            stats.var *= n as f64 / (n - 1) as f64;
+        }
+        stats
+    }
+}
+
+/// Add per-worker `values` into `acc` element-wise, growing `acc` as needed.
+pub fn accumulate(acc: &mut Vec<f64>, values: &[f64]) {
+    if acc.len() < values.len() {
+        acc.resize(values.len(), 0.0);
+    }
+    for (a, v) in acc.iter_mut().zip(values) {
+        *a += v;
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Significance
+// ---------------------------------------------------------------------------
+
+/// A metric as a sample of `n` values (workers, or repeated runs): mean and
+/// unbiased variance.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Sample {
+    pub mean: f64,
+    pub var: f64,
+    pub n: usize,
+}
+
+impl Sample {
+    /// From stats over `n` values built by `Stats::from_values` (whose variance
+    /// divides by `n`, not `n - 1`).
+    pub fn from_stats(stats: &Stats, n: usize) -> Self {
+        let var = if n > 1 {
+            stats.var * n as f64 / (n - 1) as f64
+        } else {
+            0.0
+        };
+        Self {
+            mean: stats.mean,
+            var,
+            n,
+        }
+    }
+}
+
+/// Welch's t-test of the difference `new.mean - base.mean`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Significance {
+    /// Two-sided p-value.
+    pub p_value: f64,
+    /// Confidence interval of the difference.
+    pub ci_low: f64,
+    pub ci_high: f64,
+    /// `p_value < 1 - confidence`.
+    pub significant: bool,
+}
+
+/// Compare two samples at `confidence` (e.g. 0.95). None when either side has fewer
+/// than two values. Two samples without spread differ significantly iff their means
+/// differ.
+pub fn welch_test(base: &Sample, new: &Sample, confidence: f64) -> Option<Significance> {
+    if base.n < 2 || new.n < 2 {
+        return None;
+    }
+    let diff = new.mean - base.mean;
+    let (vb, vn) = (base.var / base.n as f64, new.var / new.n as f64);
+    let se = (vb + vn).sqrt();
+    if se == 0.0 {
+        let p_value = if diff == 0.0 { 1.0 } else { 0.0 };
+        return Some(Significance {
+            p_value,
+            ci_low: diff,
+            ci_high: diff,
+            significant: diff != 0.0,
+        });
+    }
+    // Welch–Satterthwaite degrees of freedom.
+    let df =
+        (vb + vn).powi(2) / (vb.powi(2) / (base.n - 1) as f64 + vn.powi(2) / (new.n - 1) as f64);
+    let p_value = t_two_sided_p(diff / se, df);
+    let half = t_critical(1.0 - confidence, df) * se;
+    Some(Significance {
+        p_value,
+        ci_low: diff - half,
+        ci_high: diff + half,
+        significant: p_value < 1.0 - confidence,
+    })
+}
+
+/// P(|T| >= |t|) for Student's t with `df` degrees of freedom.
+fn t_two_sided_p(t: f64, df: f64) -> f64 {
+    reg_inc_beta(df / 2.0, 0.5, df / (df + t * t))
+}
+
+/// The `t` with `t_two_sided_p(t, df) == alpha`, by bisection.
+fn t_critical(alpha: f64, df: f64) -> f64 {
+    let (mut lo, mut hi) = (0.0, 1e6);
+    for _ in 0..200 {
+        let mid = (lo + hi) / 2.0;
+        if t_two_sided_p(mid, df) > alpha {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
     }
+    (lo + hi) / 2.0
 }
 
-/// Adding two Stats sums means and variances (valid for independent variables).
-/// Min/max are also summed, providing conservative bounds.
-impl Add for &Stats {
-    type Output = Stats;
-    fn add(self, rhs: &Stats) -> Stats {
-        Stats {
-            mean: self.mean + rhs.mean,
-            var: self.var + rhs.var,
-            min: self.min + rhs.min,
-            max: self.max + rhs.max,
+/// Regularized incomplete beta function I_x(a, b), by its continued fraction
+/// (Numerical Recipes, `betai`).
+fn reg_inc_beta(a: f64, b: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+    let front =
+        (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp();
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_cont_frac(a, b, x) / a
+    } else {
+        1.0 - front * beta_cont_frac(b, a, 1.0 - x) / b
+    }
+}
+
+fn beta_cont_frac(a: f64, b: f64, x: f64) -> f64 {
+    const TINY: f64 = 1e-300;
+    let (qab, qap, qam) = (a + b, a + 1.0, a - 1.0);
+    let mut c = 1.0;
+    let mut d = 1.0 - qab * x / qap;
+    if d.abs() < TINY {
+        d = TINY;
+    }
+    d = 1.0 / d;
+    let mut h = d;
+    for m in 1..300 {
+        let m = m as f64;
+        let m2 = 2.0 * m;
+        for aa in [
+            m * (b - m) * x / ((qam + m2) * (a + m2)),
+            -(a + m) * (qab + m) * x / ((a + m2) * (qap + m2)),
+        ] {
+            d = 1.0 + aa * d;
+            if d.abs() < TINY {
+                d = TINY;
+            }
+            c = 1.0 + aa / c;
+            if c.abs() < TINY {
+                c = TINY;
+            }
+            d = 1.0 / d;
+            h *= d * c;
+        }
+        if (d * c - 1.0).abs() < 1e-15 {
+            break;
+        }
+    }
+    h
+}
+
+/// ln Γ(x) for x > 0 (Lanczos approximation, g = 7).
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        // Reflection formula.
+        return (std::f64::consts::PI / (std::f64::consts::PI * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let sum = COEFFS[1..]
+        .iter()
+        .enumerate()
+        .fold(COEFFS[0], |acc, (i, c)| acc + c / (x + i as f64 + 1.0));
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{} is not within {} of {}",
+            actual,
+            tolerance,
+            expected
+        );
+    }
+
+    fn sample(values: &[f64]) -> Sample {
+        Sample::from_stats(&Stats::from_values(values), values.len())
+    }
+
+    #[test]
+    fn stats_use_population_variance_and_samples_unbiased() {
+        let stats = Stats::from_values(&[1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(
+            (stats.mean, stats.var, stats.min, stats.max),
+            (2.5, 1.25, 1.0, 4.0)
+        );
+        let s = Sample::from_stats(&stats, 4);
+        assert_close(s.var, 5.0 / 3.0, 1e-12);
+        assert_close(
+            Stats::from_sample(&[1.0, 2.0, 3.0, 4.0]).var,
+            5.0 / 3.0,
+            1e-12,
+        );
+        assert_eq!(Sample::from_stats(&Stats::from_values(&[7.0]), 1).var, 0.0);
+    }
+
+    #[test]
+    fn welch_test_matches_a_hand_checked_case() {
+        let base = sample(&[1.0, 2.0, 3.0, 4.0, 5.0]);
+        let new = sample(&[0.0, 6.0, 7.0, 7.0, 9.0, 9.0]);
+        let sig = welch_test(&base, &new, 0.95).unwrap();
+        assert_close(sig.p_value, 0.0639, 5e-5);
+        assert_close(sig.ci_low, -0.248, 5e-4);
+        assert_close(sig.ci_high, 6.915, 5e-4);
+        assert!(!sig.significant);
+        // The same difference is significant at a lower confidence.
+        assert!(welch_test(&base, &new, 0.9).unwrap().significant);
+    }
+
+    #[test]
+    fn welch_test_needs_two_values_per_side() {
+        let two = sample(&[1.0, 2.0]);
+        let one = sample(&[5.0]);
+        assert!(welch_test(&one, &two, 0.95).is_none());
+        assert!(welch_test(&two, &one, 0.95).is_none());
+        assert!(welch_test(&Sample::default(), &two, 0.95).is_none());
+    }
+
+    #[test]
+    fn welch_test_without_spread_compares_means() {
+        let base = sample(&[3.0, 3.0, 3.0]);
+        let same = welch_test(&base, &sample(&[3.0, 3.0]), 0.95).unwrap();
+        assert_eq!(
+            (same.p_value, same.ci_low, same.ci_high, same.significant),
+            (1.0, 0.0, 0.0, false)
+        );
+        let moved = welch_test(&base, &sample(&[4.0, 4.0]), 0.95).unwrap();
+        assert_eq!(
+            (
+                moved.p_value,
+                moved.ci_low,
+                moved.ci_high,
+                moved.significant
+            ),
+            (0.0, 1.0, 1.0, true)
+        );
+    }
+
+    #[test]
+    fn t_distribution_matches_tables() {
+        // Two-sided 95% critical values.
+        for (df, t) in [(1.0, 12.706), (2.0, 4.303), (10.0, 2.228), (30.0, 2.042)] {
+            assert_close(t_critical(0.05, df), t, 1e-3);
+            assert_close(t_two_sided_p(t, df), 0.05, 1e-4);
         }
+        assert_close(t_critical(0.01, 5.0), 4.032, 1e-3);
+        assert_eq!(t_two_sided_p(0.0, 7.0), 1.0);
+    }
+
+    #[test]
+    fn t_distribution_approaches_the_normal_for_large_df() {
+        assert_close(t_critical(0.05, 1e6), 1.959_964, 1e-4);
+        assert_close(t_critical(0.01, 1e6), 2.575_829, 1e-4);
+        assert_close(t_two_sided_p(1.959_964, 1e6), 0.05, 1e-5);
+    }
+
+    #[test]
+    fn reg_inc_beta_known_values() {
+        assert_eq!(reg_inc_beta(2.0, 3.0, 0.0), 0.0);
+        assert_eq!(reg_inc_beta(2.0, 3.0, 1.0), 1.0);
+        // I_x(1, 1) = x, and I_x(a, b) = 1 - I_(1-x)(b, a).
+        assert_close(reg_inc_beta(1.0, 1.0, 0.3), 0.3, 1e-12);
+        assert_close(reg_inc_beta(2.0, 2.0, 0.5), 0.5, 1e-12);
+        assert_close(
+            reg_inc_beta(2.5, 4.0, 0.2),
+            1.0 - reg_inc_beta(4.0, 2.5, 0.8),
+            1e-12,
+        );
+        // I_x(a, 1) = x^a.
+        assert_close(reg_inc_beta(3.0, 1.0, 0.7), 0.343, 1e-12);
+    }
+
+    #[test]
+    fn ln_gamma_known_values() {
+        assert_close(ln_gamma(1.0), 0.0, 1e-12);
+        assert_close(ln_gamma(5.0), 24f64.ln(), 1e-12);
+        assert_close(ln_gamma(0.5), std::f64::consts::PI.sqrt().ln(), 1e-12);
+        // Below 0.5, through the reflection formula: Γ(0.25) = 3.625609908...
+        assert_close(ln_gamma(0.25), 3.625_609_908_221_908f64.ln(), 1e-12);
+        assert_close(ln_gamma(100.0), 359.134_205_369_575_4, 1e-9);
     }
 }
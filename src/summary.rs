@@ -0,0 +1,561 @@
+//! Plain-text summaries printed to stdout by the `summary`, `diff` and `check`
+//! subcommands, for machines without a browser.
+//!
+//! Colors come from `colored`, which turns them off for `NO_COLOR`, `CLICOLOR=0` or
+//! when stdout is not a terminal.
+
+use crate::check::Violation;
+use crate::diff::{Delta, DiffReport};
+use crate::stats::Stats;
+use crate::view::{CriticalPath, ReportData};
+
+use colored::{ColoredString, Colorize};
+use std::collections::BTreeMap;
+
+/// Print the critical paths of one snapshot, one node per line. The slowest worker's
+/// path is only listed in full when it differs from the mean path.
+pub fn print_critical_paths(label: &str, data: &ReportData) {
+    if !data.has_time {
+        return;
+    }
+    let paths = &data.critical_paths;
+    print_path(label, "mean worker time", &paths.mean, data);
+    let slowest = match paths.max_worker.worker {
+        Some(worker) => format!("slowest worker {}", worker),
+        None => "slowest worker".to_string(),
+    };
+    if paths.max_worker.nodes == paths.mean.nodes {
+        println!(
+            "{} ({}, {}): same nodes, {:.1} ms",
+            "Critical path".cyan().bold(),
+            label,
+            slowest,
+            paths.max_worker.total_ms
+        );
+    } else {
+        print_path(label, &slowest, &paths.max_worker, data);
+    }
+}
+
+fn print_path(label: &str, weight: &str, path: &CriticalPath, data: &ReportData) {
+    println!(
+        "{} ({}, {}): {:.1} ms over {} node(s)",
+        "Critical path".cyan().bold(),
+        label,
+        weight,
+        path.total_ms,
+        path.nodes.len()
+    );
+    for (name, ms) in path.nodes.iter().zip(&path.node_ms) {
+        let (block, label) = data
+            .nodes
+            .get(name)
+            .map(|n| (n.block.as_str(), n.label.as_str()))
+            .unwrap_or_default();
+        println!("  {:>10.1} ms  {:<12} {}", ms, block, label);
+    }
+}
+
+/// Rows listed per section of the diff summary.
+const DIFF_TOP: usize = 10;
+
+/// Print the total change, then the largest time changes per stratum and per node.
+pub fn print_diff(report: &DiffReport) {
+    let Some(total) = &report.totals.total_active_ms else {
+        if let Some(d) = &report.totals.batched_in {
+            println!("{} batched_in {}", "Diff".cyan().bold(), fmt_delta(d));
+        }
+        return;
+    };
+    println!("{} mapped time {}", "Diff".cyan().bold(), fmt_delta(total));
+    println!(
+        "{}",
+        format!(
+            "(± is the {:.0}% confidence interval; ~ marks changes within noise)",
+            report.confidence * 100.0
+        )
+        .dimmed()
+    );
+
+    let mut blocks: Vec<(&str, &Delta)> = report
+        .blocks
+        .iter()
+        .filter_map(|b| Some((b.name.as_str(), b.deltas.total_active_ms.as_ref()?)))
+        .collect();
+    print_top("Strata", &mut blocks);
+
+    let mut nodes: Vec<(&str, &Delta)> = report
+        .nodes
+        .iter()
+        .filter_map(|n| Some((n.label.as_str(), n.deltas.total_active_ms.as_ref()?)))
+        .collect();
+    print_top("Nodes", &mut nodes);
+
+    if !report.added_nodes.is_empty() || !report.removed_nodes.is_empty() {
+        println!(
+            "{}: {} added, {} removed",
+            "Nodes".cyan().bold(),
+            report.added_nodes.len(),
+            report.removed_nodes.len()
+        );
+    }
+}
+
+fn print_top(title: &str, rows: &mut [(&str, &Delta)]) {
+    rows.sort_by(|a, b| b.1.abs.abs().total_cmp(&a.1.abs.abs()));
+    println!("{} (largest time changes):", title.cyan().bold());
+    for (name, d) in rows.iter().take(DIFF_TOP) {
+        println!("  {}  {}", fmt_delta(d), name);
+    }
+}
+
+/// `base -> new +abs (+rel%) ±ci`, colored red when slower and green when faster;
+/// changes within noise are left uncolored and marked `~`.
+fn fmt_delta(d: &Delta) -> String {
+    let rel = d
+        .rel
+        .map_or_else(|| "new".to_string(), |r| format!("{:+.1}%", r * 100.0));
+    let ci = d.significance.as_ref().map_or_else(String::new, |s| {
+        format!(" ±{:<9.1}", (s.ci_high - s.ci_low) / 2.0)
+    });
+    let mark = if d.is_significant() { ' ' } else { '~' };
+    let change = format!("{:+10.1} ({:>7}){}{}", d.abs, rel, ci, mark);
+    let change = if !d.is_significant() {
+        change.normal()
+    } else if d.abs > 0.0 {
+        change.red()
+    } else if d.abs < 0.0 {
+        change.green()
+    } else {
+        change.normal()
+    };
+    format!("{:>10.1} -> {:>10.1} {}", d.base, d.new, change)
+}
+
+/// Print the outcome of `check`: one line per violation, or a pass line, then the
+/// changes over a threshold that are within noise.
+pub fn print_check(
+    base_label: &str,
+    new_label: &str,
+    violations: &[Violation],
+    noise: &[Violation],
+) {
+    if violations.is_empty() {
+        println!(
+            "{} no regressions over the thresholds (baseline {} vs {})",
+            "PASS".green().bold(),
+            base_label,
+            new_label
+        );
+    } else {
+        println!(
+            "{} {} regression(s) over the thresholds (baseline {} vs {}):",
+            "FAIL".red().bold(),
+            violations.len(),
+            base_label,
+            new_label
+        );
+        for v in violations {
+            println!(
+                "  {}  {} (limit {})",
+                fmt_delta(&v.delta),
+                v.subject,
+                v.limit
+            );
+        }
+    }
+    if !noise.is_empty() {
+        println!(
+            "{} {} change(s) over the thresholds within noise:",
+            "Noise".cyan().bold(),
+            noise.len()
+        );
+        for v in noise {
+            println!(
+                "  {}  {} (limit {})",
+                fmt_delta(&v.delta),
+                v.subject,
+                v.limit
+            );
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Summary subcommand
+// ---------------------------------------------------------------------------
+
+/// What the `summary` tables are ranked by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Metric {
+    /// Mean total active time (ms).
+    Time,
+    /// Mean activations.
+    Activations,
+    /// Mean batched_in.
+    #[value(name = "batched_in")]
+    BatchedIn,
+    /// Mean merges.
+    Merges,
+    /// Slowest worker's time over the mean worker's time (items with at least 1%
+    /// of the mapped time only).
+    Imbalance,
+}
+
+impl Metric {
+    pub fn name(self) -> &'static str {
+        match self {
+            Metric::Time => "time",
+            Metric::Activations => "activations",
+            Metric::BatchedIn => "batched_in",
+            Metric::Merges => "merges",
+            Metric::Imbalance => "imbalance",
+        }
+    }
+
+    /// Whether the metric comes from the memory log (otherwise the time log).
+    pub fn is_memory(self) -> bool {
+        matches!(self, Metric::BatchedIn | Metric::Merges)
+    }
+}
+
+/// Minimum share of the mapped time for an item to be ranked by imbalance, so that
+/// tiny items with one busy worker do not fill the tables.
+const IMBALANCE_MIN_SHARE: f64 = 0.01;
+
+/// Table columns, in print order: header and width.
+const COLUMNS: [(&str, usize); 6] = [
+    ("time (ms)", 11),
+    ("%time", 6),
+    ("activations", 12),
+    ("batched_in", 12),
+    ("merges", 10),
+    ("max/mean", 8),
+];
+
+/// Mean metrics of one table row; None where the log or the metric is missing.
+struct Row {
+    name: String,
+    ms: Option<f64>,
+    activations: Option<f64>,
+    batched_in: Option<f64>,
+    merges: Option<f64>,
+    /// `max / mean` of the per-worker time.
+    imbalance: Option<f64>,
+}
+
+impl Row {
+    fn new(
+        name: String,
+        data: &ReportData,
+        time: [&Stats; 2],
+        memory: Option<[&Stats; 2]>,
+    ) -> Self {
+        let [activations, ms] = time;
+        let (batched_in, merges) = match memory {
+            Some([b, m]) if data.has_memory => (Some(b.mean), Some(m.mean)),
+            _ => (None, None),
+        };
+        Self {
+            name,
+            ms: data.has_time.then_some(ms.mean),
+            activations: data.has_time.then_some(activations.mean),
+            batched_in,
+            merges,
+            imbalance: (data.has_time && ms.mean > 0.0).then(|| ms.max / ms.mean),
+        }
+    }
+
+    fn value(&self, metric: Metric) -> Option<f64> {
+        match metric {
+            Metric::Time => self.ms,
+            Metric::Activations => self.activations,
+            Metric::BatchedIn => self.batched_in,
+            Metric::Merges => self.merges,
+            Metric::Imbalance => self.imbalance,
+        }
+    }
+}
+
+/// Print the totals, coverage and critical stratum of one snapshot, then its top
+/// `top` strata, rules, nodes and operators by `metric`, fitted to the terminal.
+pub fn print_summary(label: &str, data: &ReportData, metric: Metric, top: usize) {
+    print_totals(label, data);
+    let width = terminal_width();
+    let total_ms = data.totals.total_mapped_ms.mean;
+
+    let strata: Vec<Row> = data
+        .blocks
+        .values()
+        .map(|b| {
+            let time = [&b.activations, &b.total_active_ms];
+            Row::new(
+                b.block.clone(),
+                data,
+                time,
+                Some([&b.batched_in, &b.merges]),
+            )
+        })
+        .collect();
+    print_table("Strata", "stratum", strata, data, metric, top, width);
+
+    let rules: Vec<Row> = data
+        .rules
+        .iter()
+        .map(|r| {
+            let c = &r.cost;
+            let mut row = Row::new(
+                r.text.clone(),
+                data,
+                [&c.activations, &c.total_active_ms],
+                None,
+            );
+            row.batched_in = data.has_memory.then_some(c.batched_in.mean);
+            row
+        })
+        .collect();
+    print_table("Rules", "rule", rules, data, metric, top, width);
+
+    let nodes: Vec<Row> = data
+        .nodes
+        .values()
+        .map(|n| {
+            let name = format!("{} ({})", n.label, n.block);
+            let time = [&n.self_activations, &n.self_total_active_ms];
+            let memory = n
+                .has_memory_data
+                .then_some([&n.self_batched_in, &n.self_merges]);
+            Row::new(name, data, time, memory)
+        })
+        .collect();
+    print_table("Nodes", "node", nodes, data, metric, top, width);
+
+    let operators: Vec<Row> = data
+        .nodes
+        .values()
+        .flat_map(|n| n.operators.iter().map(move |op| (n, op)))
+        .map(|(n, op)| {
+            let name = format!("{:?} {} in {} ({})", op.addr, op.op_name, n.label, n.block);
+            let memory = match (&op.batched_in, &op.merges) {
+                (Some(b), Some(m)) => Some([b, m]),
+                _ => None,
+            };
+            Row::new(name, data, [&op.activations, &op.total_active_ms], memory)
+        })
+        .collect();
+    print_table("Operators", "operator", operators, data, metric, top, width);
+
+    if metric == Metric::Imbalance && total_ms > 0.0 {
+        println!(
+            "{}",
+            format!(
+                "(max/mean is the slowest worker's time over the mean; items under {:.0}% of the mapped time are not ranked)",
+                IMBALANCE_MIN_SHARE * 100.0
+            )
+            .dimmed()
+        );
+    }
+}
+
+fn print_totals(label: &str, data: &ReportData) {
+    let t = &data.totals;
+    let mut about = vec![
+        format!("{} worker(s)", data.num_workers),
+        format!("{} nodes", t.names),
+    ];
+    if let Some(runs) = &data.runs {
+        about.push(format!("averaged over {} runs", runs.runs));
+    }
+    println!(
+        "{} {} ({})",
+        "Summary".cyan().bold(),
+        label,
+        about.join(", ")
+    );
+
+    let key = |k: &str| format!("  {:<17}", k).bold();
+    if data.has_time {
+        println!(
+            "{}{:.1} ms, {} of {} operator(s) in the time log",
+            key("mapped time"),
+            t.total_mapped_ms.mean,
+            t.operators_mapped,
+            t.operators_in_time
+        );
+        if let (Some(pct), Some(dataflow)) = (t.coverage_pct, &t.dataflow_ms) {
+            let coverage = format!("{:.1}%", pct);
+            // Same cut-off as the report's coverage pill.
+            let coverage = if pct < 90.0 {
+                coverage.yellow()
+            } else {
+                coverage.normal()
+            };
+            println!(
+                "{}{} of {:.1} ms dataflow time",
+                key("coverage"),
+                coverage,
+                dataflow.mean
+            );
+        }
+        if t.operators_unmapped > 0 {
+            println!(
+                "{}{} operator(s), {:.1} ms excluding scopes",
+                key("unmapped"),
+                t.operators_unmapped,
+                t.total_unmapped_ms.mean
+            );
+        }
+        println!(
+            "{}{:.0}",
+            key("activations"),
+            t.total_mapped_activations.mean
+        );
+    } else {
+        println!("{}{}", key("time"), "no time log".yellow());
+    }
+    if data.has_memory {
+        println!("{}{:.0}", key("batched_in"), t.total_batched_in.mean);
+    } else {
+        println!("{}{}", key("memory"), "no memory log".yellow());
+    }
+    if let Some((block, ms)) = critical_stratum(data) {
+        let path = data.critical_paths.mean.total_ms;
+        println!(
+            "{}{}: {:.1} of {:.1} ms on the critical path ({:.1}%)",
+            key("critical stratum"),
+            block,
+            ms,
+            path,
+            if path > 0.0 { ms / path * 100.0 } else { 0.0 }
+        );
+    }
+    println!("{}", "(means over workers)".dimmed());
+}
+
+/// The block with the most time on the mean-worker critical path.
+fn critical_stratum(data: &ReportData) -> Option<(&str, f64)> {
+    if !data.has_time {
+        return None;
+    }
+    let path = &data.critical_paths.mean;
+    let mut by_block: BTreeMap<&str, f64> = BTreeMap::new();
+    for (name, ms) in path.nodes.iter().zip(&path.node_ms) {
+        if let Some(node) = data.nodes.get(name) {
+            *by_block.entry(node.block.as_str()).or_default() += ms;
+        }
+    }
+    by_block.into_iter().max_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+fn print_table(
+    title: &str,
+    item: &str,
+    rows: Vec<Row>,
+    data: &ReportData,
+    metric: Metric,
+    top: usize,
+    width: Option<usize>,
+) {
+    let total_ms = data.totals.total_mapped_ms.mean;
+    let count = rows.len();
+    let mut rows: Vec<(f64, Row)> = rows
+        .into_iter()
+        .filter(|r| {
+            metric != Metric::Imbalance || r.ms.unwrap_or(0.0) >= total_ms * IMBALANCE_MIN_SHARE
+        })
+        .filter_map(|r| Some((r.value(metric)?, r)))
+        .collect();
+    println!();
+    if rows.is_empty() {
+        println!(
+            "{} {}",
+            format!("{} by {}:", title, metric.name()).cyan().bold(),
+            format!("no {} with {} data", item, metric.name()).dimmed()
+        );
+        return;
+    }
+    rows.sort_by(|a, b| b.0.total_cmp(&a.0));
+    println!(
+        "{} (top {} of {})",
+        format!("{} by {}", title, metric.name()).cyan().bold(),
+        top.min(rows.len()),
+        count
+    );
+
+    let sorted = match metric {
+        Metric::Time => 0,
+        Metric::Activations => 2,
+        Metric::BatchedIn => 3,
+        Metric::Merges => 4,
+        Metric::Imbalance => 5,
+    };
+    let header: Vec<ColoredString> = COLUMNS
+        .iter()
+        .enumerate()
+        .map(|(i, (name, w))| {
+            let cell = format!("{:>w$}", name, w = w);
+            if i == sorted {
+                cell.bold()
+            } else {
+                cell.dimmed()
+            }
+        })
+        .collect();
+    println!("  {}  {}", join(&header), item.dimmed());
+
+    let fixed: usize = 2 + COLUMNS.iter().map(|(_, w)| w + 2).sum::<usize>();
+    let name_width = width.map(|w| w.saturating_sub(fixed).max(20));
+    for (_, row) in rows.iter().take(top) {
+        let values = [
+            row.ms.map(|v| format!("{:.1}", v)),
+            row.ms
+                .filter(|_| total_ms > 0.0)
+                .map(|v| format!("{:.1}%", v / total_ms * 100.0)),
+            row.activations.map(|v| format!("{:.0}", v)),
+            row.batched_in.map(|v| format!("{:.0}", v)),
+            row.merges.map(|v| format!("{:.0}", v)),
+            row.imbalance.map(|v| format!("{:.2}", v)),
+        ];
+        let cells: Vec<ColoredString> = values
+            .into_iter()
+            .zip(COLUMNS)
+            .enumerate()
+            .map(|(i, (value, (_, w)))| {
+                let cell = format!("{:>w$}", value.as_deref().unwrap_or("-"), w = w);
+                if i == sorted {
+                    cell.bold()
+                } else {
+                    cell.normal()
+                }
+            })
+            .collect();
+        println!("  {}  {}", join(&cells), truncate(&row.name, name_width));
+    }
+}
+
+fn join(cells: &[ColoredString]) -> String {
+    cells
+        .iter()
+        .map(|c| c.to_string())
+        .collect::<Vec<_>>()
+        .join("  ")
+}
+
+/// Width to fit the tables into: the terminal's, else `$COLUMNS`, else none (piped
+/// output keeps full names).
+fn terminal_width() -> Option<usize> {
+    terminal_size::terminal_size()
+        .map(|(w, _)| w.0 as usize)
+        .or_else(|| std::env::var("COLUMNS").ok()?.parse().ok())
+}
+
+fn truncate(text: &str, width: Option<usize>) -> String {
+    match width {
+        Some(w) if text.chars().count() > w => {
+            let mut cut: String = text.chars().take(w.saturating_sub(1)).collect();
+            cut.push('…');
+            cut
+        }
+        _ => text.to_string(),
+    }
+}
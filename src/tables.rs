@@ -0,0 +1,263 @@
+//! Flat CSV/TSV tables of the analysis model, for spreadsheets and pandas.
+//!
+//! `--format csv` (or `tsv`) writes a directory with one file per table: `nodes`,
+//! `operators` and `rules`, each with one row per snapshot and item. Every metric
+//! gets `<metric>_mean`, `_var`, `_min` and `_max` columns (stats over workers, as in
+//! the report). Cells of a log the run does not have, or of an operator without a
+//! memory row, are empty rather than zero.
+
+use crate::Result;
+use crate::diagnostics;
+use crate::stats::Stats;
+use crate::view::ReportData;
+
+use anyhow::Context;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+const TIME_METRICS: [&str; 2] = ["activations", "total_active_ms"];
+const MEMORY_METRICS: [&str; 5] = ["batched_in", "merges", "merge_in", "merge_out", "dropped"];
+
+/// Write `nodes.<extension>`, `operators.<extension>` and `rules.<extension>` into
+/// `dir` (created if missing), with fields separated by `delimiter`.
+pub fn write_tables(
+    dir: &str,
+    delimiter: u8,
+    extension: &str,
+    labels: &[String],
+    snapshots: &[ReportData],
+) -> Result<()> {
+    fs::create_dir_all(dir)
+        .with_context(|| diagnostics::error_message(format!("create output directory {}", dir)))?;
+    let path = |table: &str| Path::new(dir).join(format!("{}.{}", table, extension));
+    let (header, rows) = node_table(labels, snapshots);
+    write_table(&path("nodes"), delimiter, &header, &rows)?;
+    let (header, rows) = operator_table(labels, snapshots);
+    write_table(&path("operators"), delimiter, &header, &rows)?;
+    let (header, rows) = rule_table(labels, snapshots);
+    write_table(&path("rules"), delimiter, &header, &rows)
+}
+
+fn write_table(path: &Path, delimiter: u8, header: &[String], rows: &[Vec<String>]) -> Result<()> {
+    let context = || diagnostics::error_message(format!("write table {}", path.display()));
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_path(path)
+        .with_context(context)?;
+    writer.write_record(header).with_context(context)?;
+    for row in rows {
+        writer.write_record(row).with_context(context)?;
+    }
+    writer.flush().with_context(context)
+}
+
+// ---------------------------------------------------------------------------
+// Tables
+// ---------------------------------------------------------------------------
+
+type Table = (Vec<String>, Vec<Vec<String>>);
+
+/// One row per snapshot and node, with the node's own (not inclusive) cost.
+fn node_table(labels: &[String], snapshots: &[ReportData]) -> Table {
+    let mut header = columns(&[
+        "snapshot",
+        "node",
+        "label",
+        "block",
+        "fingerprint",
+        "tags",
+        "operators",
+        "num_workers",
+    ]);
+    push_metric_columns(&mut header, &TIME_METRICS);
+    push_metric_columns(&mut header, &MEMORY_METRICS);
+
+    let mut rows = Vec::new();
+    for (label, data) in labels.iter().zip(snapshots) {
+        for node in data.nodes.values() {
+            let mut row = vec![
+                label.clone(),
+                node.name.clone(),
+                node.label.clone(),
+                node.block.clone(),
+                node.fingerprint.clone().unwrap_or_default(),
+                node.tags.join(" "),
+                node.operators.len().to_string(),
+                node.num_workers.to_string(),
+            ];
+            for stats in [&node.self_activations, &node.self_total_active_ms] {
+                push_stats(&mut row, data.has_time.then_some(stats));
+            }
+            let has_memory = data.has_memory && node.has_memory_data;
+            for stats in [
+                &node.self_batched_in,
+                &node.self_merges,
+                &node.self_merge_in,
+                &node.self_merge_out,
+                &node.self_dropped,
+            ] {
+                push_stats(&mut row, has_memory.then_some(stats));
+            }
+            rows.push(row);
+        }
+    }
+    (header, rows)
+}
+
+/// One row per snapshot and mapped operator, including extra log columns (the union
+/// over snapshots, so every row has the same columns).
+fn operator_table(labels: &[String], snapshots: &[ReportData]) -> Table {
+    let extra: BTreeSet<&String> = snapshots.iter().flat_map(|d| &d.extra_metrics).collect();
+    let mut header = columns(&[
+        "snapshot",
+        "node",
+        "label",
+        "block",
+        "fingerprint",
+        "addr",
+        "op_name",
+    ]);
+    push_metric_columns(&mut header, &TIME_METRICS);
+    push_metric_columns(&mut header, &MEMORY_METRICS);
+    for name in &extra {
+        push_metric_columns(&mut header, &[name.as_str()]);
+    }
+
+    let mut rows = Vec::new();
+    for (label, data) in labels.iter().zip(snapshots) {
+        for node in data.nodes.values() {
+            for op in &node.operators {
+                let addr: Vec<String> = op.addr.iter().map(|a| a.to_string()).collect();
+                let mut row = vec![
+                    label.clone(),
+                    node.name.clone(),
+                    node.label.clone(),
+                    node.block.clone(),
+                    node.fingerprint.clone().unwrap_or_default(),
+                    format!("[{}]", addr.join(", ")),
+                    op.op_name.clone(),
+                ];
+                for stats in [&op.activations, &op.total_active_ms] {
+                    push_stats(&mut row, data.has_time.then_some(stats));
+                }
+                for stats in [
+                    &op.batched_in,
+                    &op.merges,
+                    &op.merge_in,
+                    &op.merge_out,
+                    &op.dropped,
+                ] {
+                    push_stats(&mut row, stats.as_ref());
+                }
+                for name in &extra {
+                    push_stats(&mut row, op.extra.get(*name));
+                }
+                rows.push(row);
+            }
+        }
+    }
+    (header, rows)
+}
+
+/// One row per snapshot and rule (in ops.json order), with the cost of the nodes
+/// its plan maps to under the run's `--shared-policy`.
+fn rule_table(labels: &[String], snapshots: &[ReportData]) -> Table {
+    let mut header = columns(&["snapshot", "rule", "text", "root", "nodes", "shared_nodes"]);
+    push_metric_columns(&mut header, &TIME_METRICS);
+    push_metric_columns(&mut header, &["batched_in"]);
+
+    let mut rows = Vec::new();
+    for (label, data) in labels.iter().zip(snapshots) {
+        for (i, rule) in data.rules.iter().enumerate() {
+            let cost = &rule.cost;
+            let mut row = vec![
+                label.clone(),
+                i.to_string(),
+                rule.text.clone(),
+                rule.root.clone(),
+                cost.nodes.to_string(),
+                cost.shared_nodes.to_string(),
+            ];
+            for stats in [&cost.activations, &cost.total_active_ms] {
+                push_stats(&mut row, data.has_time.then_some(stats));
+            }
+            push_stats(&mut row, data.has_memory.then_some(&cost.batched_in));
+            rows.push(row);
+        }
+    }
+    (header, rows)
+}
+
+// ---------------------------------------------------------------------------
+// Cells
+// ---------------------------------------------------------------------------
+
+fn columns(names: &[&str]) -> Vec<String> {
+    names.iter().map(|n| n.to_string()).collect()
+}
+
+fn push_metric_columns(header: &mut Vec<String>, metrics: &[&str]) {
+    for metric in metrics {
+        for stat in ["mean", "var", "min", "max"] {
+            header.push(format!("{}_{}", metric, stat));
+        }
+    }
+}
+
+/// Four cells: mean, var, min, max, or four empty cells.
+fn push_stats(row: &mut Vec<String>, stats: Option<&Stats>) {
+    match stats {
+        Some(s) => row.extend([s.mean, s.var, s.min, s.max].map(|v| v.to_string())),
+        None => row.extend(std::iter::repeat_n(String::new(), 4)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::view::SharedPolicy;
+    use crate::view::tests::{program, report, time};
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    /// The node table's header and rows as written with `delimiter`.
+    fn node_lines(delimiter: u8, extension: &str) -> Vec<String> {
+        let program = program(json!({
+            "nodes": [
+                { "id": 0, "name": "join, left", "block": "stratum 0", "operators": [[0, 1]] },
+                { "id": 1, "name": "map\tright", "block": "stratum 0", "operators": [[0, 2]] },
+            ],
+        }));
+        let time = time(&[(&[0, 1], "Join", [1.0, 3.0]), (&[0, 2], "Map", [2.0, 2.0])]);
+        let data = report(&program, &time, SharedPolicy::Split);
+
+        let dir = std::env::temp_dir().join(format!(
+            "flowlog-profile-viz-tables-{}-{}",
+            extension,
+            std::process::id()
+        ));
+        let dir = dir.to_str().unwrap();
+        write_tables(dir, delimiter, extension, &["t0".to_string()], &[data]).unwrap();
+        let text = fs::read_to_string(Path::new(dir).join(format!("nodes.{}", extension)));
+        fs::remove_dir_all(dir).unwrap();
+        text.unwrap().lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn csv_quotes_labels_with_commas() {
+        let lines = node_lines(b',', "csv");
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("snapshot,node,label,block,fingerprint,"));
+        assert!(lines[1].starts_with("t0,0,\"join, left\",stratum 0,"));
+        assert!(lines[2].starts_with("t0,1,map\tright,stratum 0,"));
+    }
+
+    #[test]
+    fn tsv_quotes_labels_with_tabs() {
+        let lines = node_lines(b'\t', "tsv");
+        assert!(lines[0].starts_with("snapshot\tnode\tlabel\tblock\tfingerprint\t"));
+        assert!(lines[1].starts_with("t0\t0\tjoin, left\tstratum 0\t"));
+        assert!(lines[2].starts_with("t0\t1\t\"map\tright\"\tstratum 0\t"));
+    }
+}
@@ -0,0 +1,263 @@
+//! Chrome Trace Event Format export of the snapshot intervals, for Perfetto UI and
+//! chrome://tracing.
+//!
+//! The logs hold cumulative active time per operator and worker, not timestamps, so
+//! the timeline is synthetic. Each snapshot interval starts when the slowest worker
+//! finished the previous one. Within an interval, every worker (one track each)
+//! runs its strata in order, and within each stratum its nodes by decreasing time,
+//! back to back for the time it spent on them. A worker's idle gap before the next
+//! interval is how far it trails the slowest worker.
+
+use crate::Result;
+use crate::diagnostics;
+use crate::view::ReportData;
+
+use anyhow::bail;
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::collections::BTreeSet;
+
+/// All events go to one process; workers are its threads.
+const PID: u32 = 1;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Trace {
+    trace_events: Vec<Event>,
+    display_time_unit: &'static str,
+    other_data: Value,
+}
+
+/// One trace event: a complete slice (`X`) or metadata (`M`). Times are in µs.
+#[derive(Serialize)]
+struct Event {
+    name: String,
+    cat: &'static str,
+    ph: &'static str,
+    ts: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dur: Option<f64>,
+    pid: u32,
+    tid: u32,
+    args: Value,
+}
+
+impl Event {
+    fn slice(name: &str, cat: &'static str, tid: u32, ts: f64, dur: f64, args: Value) -> Self {
+        Self {
+            name: name.to_string(),
+            cat,
+            ph: "X",
+            ts,
+            dur: Some(dur),
+            pid: PID,
+            tid,
+            args,
+        }
+    }
+
+    fn metadata(name: &str, tid: u32, args: Value) -> Self {
+        Self {
+            name: name.to_string(),
+            cat: "__metadata",
+            ph: "M",
+            ts: 0.0,
+            dur: None,
+            pid: PID,
+            tid,
+            args,
+        }
+    }
+}
+
+/// Render the trace of `intervals` (the interval snapshots, or the snapshots
+/// themselves when there is only one), labeled by `labels`.
+pub fn render_trace(labels: &[String], intervals: &[ReportData]) -> Result<String> {
+    if !intervals.iter().any(|d| d.has_time) {
+        bail!(
+            "{}",
+            diagnostics::error_message("--format trace needs a time log")
+        );
+    }
+
+    let mut events = vec![Event::metadata(
+        "process_name",
+        0,
+        json!({ "name": "FlowLog dataflow (synthetic active-time timeline)" }),
+    )];
+    let mut workers: BTreeSet<u32> = BTreeSet::new();
+    let mut start = 0.0;
+    for (label, data) in labels.iter().zip(intervals) {
+        if !data.has_time {
+            continue;
+        }
+        let mut blocks: Vec<_> = data.blocks.values().collect();
+        blocks.sort_by(|a, b| {
+            (block_rank(&a.block), &a.block).cmp(&(block_rank(&b.block), &b.block))
+        });
+
+        let mut slowest: f64 = 0.0;
+        for (pos, &worker) in data.workers.iter().enumerate() {
+            workers.insert(worker);
+            let ms_of = |name: &String| {
+                data.nodes
+                    .get(name)
+                    .and_then(|n| n.workers.total_active_ms.get(pos))
+                    .map_or(0.0, |ms| ms * 1000.0)
+            };
+            // Slices are laid out from the node durations, so that parents exactly
+            // cover their children.
+            let mut ts = start;
+            let mut worker_events = Vec::new();
+            for block in &blocks {
+                let mut names: Vec<(&String, f64)> = block
+                    .names
+                    .iter()
+                    .map(|n| (n, ms_of(n)))
+                    .filter(|(_, us)| *us > 0.0)
+                    .collect();
+                if names.is_empty() {
+                    continue;
+                }
+                names.sort_by(|a, b| b.1.total_cmp(&a.1));
+                let block_us: f64 = names.iter().map(|(_, us)| us).sum();
+                worker_events.push(Event::slice(
+                    &block.block,
+                    "stratum",
+                    worker,
+                    ts,
+                    block_us,
+                    json!({ "snapshot": label, "ms": block_us / 1000.0 }),
+                ));
+                let mut node_ts = ts;
+                for (name, us) in names {
+                    let node = &data.nodes[name];
+                    let activations = node.workers.activations.get(pos).copied().unwrap_or(0.0);
+                    worker_events.push(Event::slice(
+                        &node.label,
+                        "node",
+                        worker,
+                        node_ts,
+                        us,
+                        json!({
+                            "snapshot": label,
+                            "node": name,
+                            "block": node.block,
+                            "ms": us / 1000.0,
+                            "activations": activations,
+                        }),
+                    ));
+                    node_ts += us;
+                }
+                ts += block_us;
+            }
+            let total_us = ts - start;
+            if total_us > 0.0 {
+                events.push(Event::slice(
+                    label,
+                    "interval",
+                    worker,
+                    start,
+                    total_us,
+                    json!({ "snapshot": label, "ms": total_us / 1000.0 }),
+                ));
+                events.extend(worker_events);
+            }
+            slowest = slowest.max(total_us);
+        }
+        start += slowest;
+    }
+
+    for &worker in &workers {
+        events.push(Event::metadata(
+            "thread_name",
+            worker,
+            json!({ "name": format!("worker {}", worker) }),
+        ));
+        events.push(Event::metadata(
+            "thread_sort_index",
+            worker,
+            json!({ "sort_index": worker }),
+        ));
+    }
+
+    let trace = Trace {
+        trace_events: events,
+        display_time_unit: "ms",
+        other_data: json!({
+            "generator": format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+            "snapshot_labels": labels,
+            "timeline": "synthetic: per-worker active time laid out back to back; intervals start when the slowest worker finished the previous one",
+        }),
+    };
+    Ok(serde_json::to_string(&trace)?)
+}
+
+/// Execution order of blocks: inputs, strata by number, then inspection (as the
+/// report orders them).
+fn block_rank(block: &str) -> u32 {
+    let b = block.to_lowercase();
+    if b.starts_with("input") {
+        return 0;
+    }
+    if let Some(n) = b
+        .strip_prefix("stratum")
+        .and_then(|rest| rest.trim().parse::<u32>().ok())
+    {
+        return 1 + n;
+    }
+    if b.contains("inspect") {
+        return 1_000_000;
+    }
+    500_000
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::view::SharedPolicy;
+    use crate::view::tests::{program, report, time};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn each_interval_starts_when_the_slowest_worker_finished_the_last() {
+        let program = program(json!({
+            "nodes": [
+                { "id": 0, "block": "stratum 0", "operators": [[0, 1]] },
+                { "id": 1, "block": "stratum 1", "operators": [[0, 2]], "parents": [0] },
+            ],
+        }));
+        // Worker 1 is the slowest in t0 (5 ms), worker 0 in t1.
+        let t0 = time(&[(&[0, 1], "Join", [1.0, 2.0]), (&[0, 2], "Map", [2.0, 3.0])]);
+        let t1 = time(&[(&[0, 1], "Join", [4.0, 1.0]), (&[0, 2], "Map", [1.0, 1.0])]);
+        let intervals = [t0, t1].map(|t| report(&program, &t, SharedPolicy::Split));
+        let labels = ["t0".to_string(), "t1".to_string()];
+
+        let trace: Value =
+            serde_json::from_str(&render_trace(&labels, &intervals).unwrap()).unwrap();
+        let slices: Vec<(&str, u64, f64, f64)> = trace["traceEvents"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|e| e["cat"] == "interval")
+            .map(|e| {
+                let f = |key: &str| e[key].as_f64().unwrap();
+                (
+                    e["name"].as_str().unwrap(),
+                    e["tid"].as_u64().unwrap(),
+                    f("ts"),
+                    f("dur"),
+                )
+            })
+            .collect();
+        assert_eq!(
+            slices,
+            vec![
+                ("t0", 0, 0.0, 3000.0),
+                ("t0", 1, 0.0, 5000.0),
+                ("t1", 0, 5000.0, 5000.0),
+                ("t1", 1, 5000.0, 2000.0),
+            ]
+        );
+    }
+}
@@ -1,18 +1,85 @@
 //! Aggregation model: combine UI tree (from ops.json) with time and memory logs.
 
-use crate::addr::Addr;
-use crate::diagnostics;
-use crate::log::{MemoryIndex, TimeIndex};
-use crate::ops::{NodeSpec, RuleSpec};
-use crate::stats::Stats;
 use crate::Result;
+use crate::addr::Addr;
+use crate::datalog::{Relation, RelationGraph};
+use crate::diagnostics::Diagnostics;
+use crate::log::{MemoryIndex, Snapshot, TimeIndex};
+use crate::ops::{NodeSpec, RuleSpec, ValidatedOps};
+use crate::runs::RunsView;
+use crate::scope::{self, ScopeView};
+use crate::stats::{self, Stats};
 
-use anyhow::bail;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::collections::BTreeSet;
 
-#[derive(Debug, Clone, Serialize)]
+/// The validated ops.json shared by every snapshot, keyed by node name.
+pub struct Program {
+    pub nodes: BTreeMap<String, NodeSpec>,
+    pub roots: Vec<String>,
+    pub rules: Vec<RuleSpec>,
+    /// Relations derived by `rules`, from their parsed text.
+    pub relations: RelationGraph,
+}
+
+impl Program {
+    pub fn new(ops: ValidatedOps, diags: &mut Diagnostics) -> Self {
+        let relations = RelationGraph::build(&ops.rules, diags);
+        Self {
+            nodes: ops
+                .nodes
+                .into_iter()
+                .map(|(id, node)| (id.to_string(), node))
+                .collect(),
+            roots: ops.roots.iter().map(|id| id.to_string()).collect(),
+            rules: ops.rules,
+            relations,
+        }
+    }
+}
+
+/// How the cost of a node used by several rules is attributed to those rules.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum SharedPolicy {
+    /// Count the node fully in every rule that uses it (rule costs overlap).
+    Full,
+    /// Split the node evenly between the rules that use it (rule costs add up).
+    #[default]
+    Split,
+    /// Attribute the node to the first rule (in ops.json order) that uses it.
+    First,
+}
+
+/// Per-worker values, indexed like `ReportData::workers`.
+///
+/// Memory series are empty when there is no memory data.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct WorkerSeries {
+    pub activations: Vec<f64>,
+    pub total_active_ms: Vec<f64>,
+    pub batched_in: Vec<f64>,
+    pub merges: Vec<f64>,
+    pub merge_in: Vec<f64>,
+    pub merge_out: Vec<f64>,
+    pub dropped: Vec<f64>,
+}
+
+impl WorkerSeries {
+    /// Element-wise add another series (e.g. an operator into its node).
+    fn accumulate(&mut self, other: &WorkerSeries) {
+        stats::accumulate(&mut self.activations, &other.activations);
+        stats::accumulate(&mut self.total_active_ms, &other.total_active_ms);
+        stats::accumulate(&mut self.batched_in, &other.batched_in);
+        stats::accumulate(&mut self.merges, &other.merges);
+        stats::accumulate(&mut self.merge_in, &other.merge_in);
+        stats::accumulate(&mut self.merge_out, &other.merge_out);
+        stats::accumulate(&mut self.dropped, &other.dropped);
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OperatorView {
     pub addr: Vec<u32>,
     pub op_name: String,
@@ -24,9 +91,32 @@ pub struct OperatorView {
     pub merge_in: Option<Stats>,
     pub merge_out: Option<Stats>,
     pub dropped: Option<Stats>,
+    /// Raw per-worker values behind the stats above.
+    pub workers: WorkerSeries,
+    /// Log columns beyond the known time/memory metrics, keyed by header name.
+    pub extra: BTreeMap<String, Stats>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+/// An operator found in the time or memory log that no ops.json node owns.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnmappedOperatorView {
+    pub addr: Vec<u32>,
+    pub op_name: String,
+    /// Has child operators in the logs (a region or an iterative scope), so its
+    /// time already includes theirs.
+    pub scope: bool,
+    /// None if this operator has no row in the time log.
+    pub activations: Option<Stats>,
+    pub total_active_ms: Option<Stats>,
+    /// None if this operator has no row in the memory log.
+    pub batched_in: Option<Stats>,
+    pub merges: Option<Stats>,
+    pub merge_in: Option<Stats>,
+    pub merge_out: Option<Stats>,
+    pub dropped: Option<Stats>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NameNodeView {
     pub name: String,
     pub label: String,
@@ -43,11 +133,12 @@ pub struct NameNodeView {
     /// Additional parents beyond the chosen primary parent (for DAG info).
     pub extra_parents: Vec<String>,
 
-    /// Aggregated over operators owned by this name (sum of means).
+    /// Stats over per-worker sums of the operators owned by this name, so min/max
+    /// are the fastest/slowest worker for this node.
     pub self_activations: Stats,
     pub self_total_active_ms: Stats,
 
-    /// Aggregated memory fields (summed over operators that have memory data).
+    /// Aggregated memory fields (per-worker sums over operators that have memory data).
     pub self_batched_in: Stats,
     pub self_merges: Stats,
     pub self_merge_in: Stats,
@@ -59,11 +150,31 @@ pub struct NameNodeView {
     /// Number of workers used for aggregation.
     pub num_workers: usize,
 
+    /// Per-worker sums over the operators owned by this name.
+    pub workers: WorkerSeries,
+
+    /// Inclusive costs: this node plus all DAG descendants (`downstream`), all DAG
+    /// ancestors (`upstream`), or its primary-tree subtree via `children` (`subtree`).
+    pub downstream: InclusiveCost,
+    pub upstream: InclusiveCost,
+    pub subtree: InclusiveCost,
+
     /// Operators owned by this name (sorted by addr asc).
     pub operators: Vec<OperatorView>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+/// Cost of a node together with a cone of related nodes. Each node in the cone is
+/// counted once, however many DAG paths reach it; stats are over per-worker sums.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct InclusiveCost {
+    /// Number of nodes in the cone, including the node itself.
+    pub nodes: usize,
+    pub activations: Stats,
+    pub total_active_ms: Stats,
+    pub batched_in: Stats,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RulePlanNodeView {
     pub fingerprint: String,
     pub node: Option<String>,
@@ -73,23 +184,119 @@ pub struct RulePlanNodeView {
     pub shared: bool,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RuleView {
     pub text: String,
     pub root: String,
     pub nodes: BTreeMap<String, RulePlanNodeView>,
+    pub cost: RuleCost,
 }
 
-#[derive(Debug, Clone, Serialize)]
+/// Cost of the nodes a rule's plan maps to (for a relation: the plans of all its
+/// rules), with shared nodes weighted by the `SharedPolicy`. Stats are over
+/// per-worker weighted sums.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct RuleCost {
+    /// Distinct nodes the plan maps to.
+    pub nodes: usize,
+    /// How many of those are also used by other rules.
+    pub shared_nodes: usize,
+    pub activations: Stats,
+    pub total_active_ms: Stats,
+    pub batched_in: Stats,
+}
+
+/// A relation derived by at least one rule, with the cost of all its rules.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelationView {
+    pub relation: String,
+    /// Head of its first rule, e.g. `path(x, y)`.
+    pub signature: String,
+    /// Indices into `ReportData::rules`.
+    pub rules: Vec<usize>,
+    /// Relations used in the bodies of its rules.
+    pub depends_on: Vec<String>,
+    /// The subset of `depends_on` used under negation.
+    pub negated: Vec<String>,
+    /// Input (never derived) relations that feed it, directly or transitively.
+    pub inputs: Vec<String>,
+    pub recursive: bool,
+    /// Shared nodes are weighted between relations (not rules), so two rules of
+    /// the same relation sharing a node do not split it.
+    pub cost: RuleCost,
+}
+
+/// Aggregates over all names in one block (input, stratum N, inspect, ...).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockView {
+    pub block: String,
+    pub names: Vec<String>,
+    pub activations: Stats,
+    pub total_active_ms: Stats,
+    pub batched_in: Stats,
+    pub merges: Stats,
+    pub merge_in: Stats,
+    pub merge_out: Stats,
+    pub dropped: Stats,
+    /// Per-worker sums over every operator in the block.
+    pub workers: WorkerSeries,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReportData {
     pub roots: Vec<String>,
     pub nodes: BTreeMap<String, NameNodeView>,
+    pub blocks: BTreeMap<String, BlockView>,
     pub rules: Vec<RuleView>,
+    /// Derived relations, in the order of their first rule.
+    pub relations: Vec<RelationView>,
+    /// Logged operators without a node, most expensive first (dataflow roots excluded).
+    pub unmapped: Vec<UnmappedOperatorView>,
+    /// Timely scopes from the operator addresses, sorted by address.
+    pub scopes: Vec<ScopeView>,
+    /// Run-to-run variation when several runs were averaged into this snapshot.
+    pub runs: Option<RunsView>,
     pub totals: TotalsView,
     pub num_workers: usize,
+    /// Worker id (from the log filenames, ascending) for each position in a `WorkerSeries`.
+    /// This is the union of the workers seen in the time and memory logs.
+    pub workers: Vec<u32>,
+    /// Whether this snapshot has time / memory log data. A report may be built from
+    /// either log alone, and a snapshot may be present in only one of the folders.
+    pub has_time: bool,
+    pub has_memory: bool,
+    /// Names of the extra log columns found on any mapped operator (sorted).
+    pub extra_metrics: Vec<String>,
+    /// Most expensive source-to-sink chains of nodes in the DAG.
+    pub critical_paths: CriticalPaths,
+    /// Policy used for `RuleView::cost` and `RelationView::cost`.
+    pub shared_policy: SharedPolicy,
 }
 
-#[derive(Debug, Clone, Serialize)]
+/// Heaviest DAG paths, from an input node to a node without children (e.g. an
+/// inspect node), by two per-node weights.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CriticalPaths {
+    /// Weighted by each node's mean-over-workers time (`self_total_active_ms.mean`).
+    pub mean: CriticalPath,
+    /// The slowest worker's path: weighted by one worker's own time, for the worker
+    /// whose heaviest path is the heaviest.
+    pub max_worker: CriticalPath,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct CriticalPath {
+    /// Node names, source first.
+    pub nodes: Vec<String>,
+    /// Weight (ms) of each node on the path.
+    pub node_ms: Vec<f64>,
+    pub total_ms: f64,
+    /// Worker id the path is weighted by (None for the mean path).
+    #[serde(default)]
+    pub worker: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TotalsView {
     pub names: usize,
     pub operators_in_time: usize,
@@ -97,32 +304,63 @@ pub struct TotalsView {
     pub total_mapped_ms: Stats,
     pub total_mapped_activations: Stats,
     pub total_batched_in: Stats,
+    /// Logged operators (dataflow roots excluded) that no node owns.
+    pub operators_unmapped: usize,
+    /// Time of the unmapped operators that are not scopes (scope time overlaps
+    /// with its children's).
+    pub total_unmapped_ms: Stats,
+    /// Time of the dataflow root rows (`[0] Dataflow`); None without such a row.
+    pub dataflow_ms: Option<Stats>,
+    /// Mean mapped time as a percentage of mean dataflow time.
+    pub coverage_pct: Option<f64>,
 }
 
 /// Build report data. Performs:
-/// - detect operator addr assigned to multiple names (error)
-/// - warn (stderr) about mapped addrs missing from time log
+/// - detect operator addr assigned to multiple names (error; lenient keeps the first name)
+/// - warn about mapped addrs missing from time log (when there is one)
 /// - validate that wherever an addr appears in both logs, the op_name agrees
+///   (error; lenient keeps the time log's op_name)
 pub fn build_report_data(
-    nodes_spec: &BTreeMap<String, NodeSpec>,
-    roots: &[String],
-    rules_spec: &[RuleSpec],
-    fingerprint_to_node: &BTreeMap<String, String>,
-    time: &TimeIndex,
-    memory: &MemoryIndex,
+    program: &Program,
+    time_snapshot: Option<&Snapshot<TimeIndex>>,
+    memory_snapshot: Option<&Snapshot<MemoryIndex>>,
+    shared_policy: SharedPolicy,
+    diags: &mut Diagnostics,
 ) -> Result<ReportData> {
+    let nodes_spec = &program.nodes;
+    let label = time_snapshot
+        .map(|s| s.label.as_str())
+        .or(memory_snapshot.map(|s| s.label.as_str()))
+        .unwrap_or_default();
+    let (empty_time, empty_memory) = (TimeIndex::new(), MemoryIndex::new());
+    let (time_workers, time) = match time_snapshot {
+        Some(s) => (s.workers.as_slice(), &s.data),
+        None => (&[][..], &empty_time),
+    };
+    let (memory_workers, memory) = match memory_snapshot {
+        Some(s) => (s.workers.as_slice(), &s.data),
+        None => (&[][..], &empty_memory),
+    };
+    let workers: Vec<u32> = time_workers
+        .iter()
+        .chain(memory_workers)
+        .copied()
+        .collect::<BTreeSet<u32>>()
+        .into_iter()
+        .collect();
+
     // Phase 0: cross-validate op_name alignment between time log and memory log.
     for (addr, mr) in memory {
-        if let Some(tr) = time.get(addr) {
-            if tr.op_name != mr.op_name {
-                bail!(
-                    "{}",
-                    diagnostics::error_message(format!(
-                        "op_name mismatch at addr {:?}: time log has {:?} but memory log has {:?}",
-                        addr.0, tr.op_name, mr.op_name
-                    ))
-                );
-            }
+        if let Some(tr) = time.get(addr)
+            && tr.op_name != mr.op_name
+        {
+            diags.error(
+                None,
+                format!(
+                    "snapshot {}: op_name mismatch at addr {:?}: time log has {:?} but memory log has {:?}",
+                    label, addr.0, tr.op_name, mr.op_name
+                ),
+            )?;
         }
     }
 
@@ -130,15 +368,17 @@ pub fn build_report_data(
     let mut owner: BTreeMap<&Addr, &str> = BTreeMap::new();
     for (name, spec) in nodes_spec {
         for addr in &spec.operators {
-            if let Some(prev) = owner.insert(addr, name.as_str()) {
-                bail!(
-                    "{}",
-                    diagnostics::error_message(format!(
+            if let Some(prev) = owner.get(addr) {
+                diags.error(
+                    None,
+                    format!(
                         "operator addr {:?} is assigned to multiple names: {} and {}",
                         addr.0, prev, name
-                    ))
-                );
+                    ),
+                )?;
+                continue;
             }
+            owner.insert(addr, name.as_str());
         }
     }
 
@@ -178,7 +418,7 @@ pub fn build_report_data(
         kids.sort();
     }
 
-    let mut roots_set: BTreeSet<String> = roots.iter().cloned().collect();
+    let mut roots_set: BTreeSet<String> = program.roots.iter().cloned().collect();
     for (name, pp) in &primary_parent {
         if pp.is_none() {
             roots_set.insert(name.clone());
@@ -189,59 +429,76 @@ pub fn build_report_data(
     // Phase 3: build per-name operator lists + aggregates.
     let mut nodes_view: BTreeMap<String, NameNodeView> = BTreeMap::new();
 
-    let mut total_mapped_ms = Stats::default();
-    let mut total_mapped_activations = Stats::default();
+    // Node, block and total stats are all computed from per-worker sums, never by
+    // adding Stats, so that variance and min/max describe real workers.
+    let mut total_workers = WorkerSeries::default();
+    let mut block_workers: BTreeMap<String, (Vec<String>, WorkerSeries)> = BTreeMap::new();
     let mut operators_mapped = 0usize;
-    let mut total_batched_in = Stats::default();
     let mut num_workers = 0usize;
+    let mut extra_metrics: BTreeSet<String> = BTreeSet::new();
 
     for (name, spec) in nodes_spec {
         let mut operators: Vec<OperatorView> = Vec::new();
-        let mut self_ms = Stats::default();
-        let mut self_act = Stats::default();
-        let mut self_batched_in = Stats::default();
-        let mut self_merges = Stats::default();
-        let mut self_merge_in = Stats::default();
-        let mut self_merge_out = Stats::default();
-        let mut self_dropped = Stats::default();
         let mut has_memory_data = false;
+        let mut node_workers = WorkerSeries::default();
 
         for addr in &spec.operators {
+            if owner.get(addr) != Some(&name.as_str()) {
+                continue;
+            }
+            let mut op_workers = WorkerSeries::default();
+            let mut extra: BTreeMap<String, Stats> = BTreeMap::new();
             let (act_stats, ms_stats, op_name) = match time.get(addr) {
                 Some(tr) => {
-                    self_ms = &self_ms + &tr.total_active_ms;
-                    self_act = &self_act + &tr.activations;
-                    total_mapped_ms = &total_mapped_ms + &tr.total_active_ms;
-                    total_mapped_activations = &total_mapped_activations + &tr.activations;
                     operators_mapped += 1;
                     if tr.num_workers > num_workers {
                         num_workers = tr.num_workers;
                     }
-                    (tr.activations.clone(), tr.total_active_ms.clone(), tr.op_name.clone())
+                    op_workers.activations =
+                        align_workers(&tr.activations_by_worker, time_workers, &workers);
+                    op_workers.total_active_ms =
+                        align_workers(&tr.total_active_ms_by_worker, time_workers, &workers);
+                    extra.extend(tr.extra.iter().map(|(k, v)| (k.clone(), v.clone())));
+                    (
+                        tr.activations.clone(),
+                        tr.total_active_ms.clone(),
+                        tr.op_name.clone(),
+                    )
                 }
                 None => {
-                    diagnostics::warn(format!(
-                        "ops.json maps name '{}' to addr {:?}, but addr not found in time log",
-                        name, addr.0
-                    ));
+                    if time_snapshot.is_some() {
+                        diags.warning(
+                            None,
+                            format!(
+                                "snapshot {}: ops.json maps name '{}' to addr {:?}, but addr not found in time log",
+                                label, name, addr.0
+                            ),
+                        );
+                    }
                     (Stats::default(), Stats::default(), String::new())
                 }
             };
 
             let mem_row = memory.get(addr);
+            // Without a time row, the memory log is the only source of the op_name.
+            let op_name = match mem_row {
+                Some(mr) if op_name.is_empty() => mr.op_name.clone(),
+                _ => op_name,
+            };
 
             let (batched_in, merges, merge_in_s, merge_out_s, dropped) = match mem_row {
                 Some(mr) => {
                     has_memory_data = true;
-                    self_batched_in = &self_batched_in + &mr.batched_in;
-                    self_merges = &self_merges + &mr.merges;
-                    self_merge_in = &self_merge_in + &mr.merge_in;
-                    self_merge_out = &self_merge_out + &mr.merge_out;
-                    self_dropped = &self_dropped + &mr.dropped;
-                    total_batched_in = &total_batched_in + &mr.batched_in;
                     if mr.num_workers > num_workers {
                         num_workers = mr.num_workers;
                     }
+                    let align = |values: &[f64]| align_workers(values, memory_workers, &workers);
+                    op_workers.batched_in = align(&mr.batched_in_by_worker);
+                    op_workers.merges = align(&mr.merges_by_worker);
+                    op_workers.merge_in = align(&mr.merge_in_by_worker);
+                    op_workers.merge_out = align(&mr.merge_out_by_worker);
+                    op_workers.dropped = align(&mr.dropped_by_worker);
+                    extra.extend(mr.extra.iter().map(|(k, v)| (k.clone(), v.clone())));
                     (
                         Some(mr.batched_in.clone()),
                         Some(mr.merges.clone()),
@@ -253,6 +510,8 @@ pub fn build_report_data(
                 None => (None, None, None, None, None),
             };
 
+            node_workers.accumulate(&op_workers);
+            extra_metrics.extend(extra.keys().cloned());
             operators.push(OperatorView {
                 addr: addr.0.clone(),
                 op_name,
@@ -263,11 +522,18 @@ pub fn build_report_data(
                 merge_in: merge_in_s,
                 merge_out: merge_out_s,
                 dropped,
+                workers: op_workers,
+                extra,
             });
         }
 
         operators.sort_by(|a, b| a.addr.cmp(&b.addr));
 
+        total_workers.accumulate(&node_workers);
+        let (block_names, block_series) = block_workers.entry(spec.block.clone()).or_default();
+        block_names.push(name.clone());
+        block_series.accumulate(&node_workers);
+
         nodes_view.insert(
             name.clone(),
             NameNodeView {
@@ -279,45 +545,240 @@ pub fn build_report_data(
                 children: tree_children.get(name).cloned().unwrap_or_default(),
                 dag_parents: normalized_parents.get(name).cloned().unwrap_or_default(),
                 extra_parents: extra_parents.get(name).cloned().unwrap_or_default(),
-                self_activations: self_act,
-                self_total_active_ms: self_ms,
-                self_batched_in,
-                self_merges,
-                self_merge_in,
-                self_merge_out,
-                self_dropped,
+                self_activations: Stats::from_values(&node_workers.activations),
+                self_total_active_ms: Stats::from_values(&node_workers.total_active_ms),
+                self_batched_in: Stats::from_values(&node_workers.batched_in),
+                self_merges: Stats::from_values(&node_workers.merges),
+                self_merge_in: Stats::from_values(&node_workers.merge_in),
+                self_merge_out: Stats::from_values(&node_workers.merge_out),
+                self_dropped: Stats::from_values(&node_workers.dropped),
                 has_memory_data,
                 num_workers,
+                workers: node_workers,
+                downstream: InclusiveCost::default(),
+                upstream: InclusiveCost::default(),
+                subtree: InclusiveCost::default(),
                 operators,
             },
         );
     }
 
+    // Phase 4: inclusive costs over each node's downstream/upstream/subtree cone.
+    let mut dag_children: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    let mut dag_parents: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for (child, parents) in &normalized_parents {
+        for p in parents {
+            dag_children.entry(p).or_default().push(child);
+            dag_parents.entry(child).or_default().push(p);
+        }
+    }
+    let tree: BTreeMap<&str, Vec<&str>> = tree_children
+        .iter()
+        .map(|(p, kids)| (p.as_str(), kids.iter().map(String::as_str).collect()))
+        .collect();
+
+    let inclusive: Vec<(String, [InclusiveCost; 3])> = nodes_view
+        .keys()
+        .map(|name| {
+            let costs = [&dag_children, &dag_parents, &tree]
+                .map(|edges| inclusive_cost(&cone(name, edges), &nodes_view));
+            (name.clone(), costs)
+        })
+        .collect();
+    for (name, [downstream, upstream, subtree]) in inclusive {
+        if let Some(view) = nodes_view.get_mut(&name) {
+            view.downstream = downstream;
+            view.upstream = upstream;
+            view.subtree = subtree;
+        }
+    }
+
+    // Phase 5: critical paths through the DAG.
+    let critical_paths = CriticalPaths {
+        mean: critical_path(&nodes_view, &dag_parents, &dag_children, |n| {
+            n.self_total_active_ms.mean
+        }),
+        max_worker: (0..workers.len())
+            .map(|pos| CriticalPath {
+                worker: Some(workers[pos]),
+                ..critical_path(&nodes_view, &dag_parents, &dag_children, |n| {
+                    n.workers.total_active_ms.get(pos).copied().unwrap_or(0.0)
+                })
+            })
+            .max_by(|a, b| a.total_ms.total_cmp(&b.total_ms))
+            .unwrap_or_default(),
+    };
+
+    // Phase 6: per-block aggregates.
+    let blocks = block_workers
+        .into_iter()
+        .map(|(block, (names, series))| {
+            let view = BlockView {
+                block: block.clone(),
+                names,
+                activations: Stats::from_values(&series.activations),
+                total_active_ms: Stats::from_values(&series.total_active_ms),
+                batched_in: Stats::from_values(&series.batched_in),
+                merges: Stats::from_values(&series.merges),
+                merge_in: Stats::from_values(&series.merge_in),
+                merge_out: Stats::from_values(&series.merge_out),
+                dropped: Stats::from_values(&series.dropped),
+                workers: series,
+            };
+            (block, view)
+        })
+        .collect();
+
+    // Phase 7: logged operators that no node owns, and coverage of the dataflow root.
+    let logged: BTreeSet<&Addr> = time.keys().chain(memory.keys()).collect();
+    let mut unmapped: Vec<UnmappedOperatorView> = Vec::new();
+    let mut unmapped_ms_workers: Vec<f64> = Vec::new();
+    let mut dataflow_workers: Option<Vec<f64>> = None;
+    for &addr in &logged {
+        let (tr, mr) = (time.get(addr), memory.get(addr));
+        if addr.0.len() == 1 {
+            if let Some(tr) = tr {
+                let ms = align_workers(&tr.total_active_ms_by_worker, time_workers, &workers);
+                stats::accumulate(dataflow_workers.get_or_insert_default(), &ms);
+            }
+            continue;
+        }
+        if owner.contains_key(addr) {
+            continue;
+        }
+        // Descendants of `addr` sort right after it.
+        let scope = logged
+            .range::<&Addr, _>(addr..)
+            .nth(1)
+            .is_some_and(|next| next.0.starts_with(&addr.0));
+        if !scope && let Some(tr) = tr {
+            let ms = align_workers(&tr.total_active_ms_by_worker, time_workers, &workers);
+            stats::accumulate(&mut unmapped_ms_workers, &ms);
+        }
+        unmapped.push(UnmappedOperatorView {
+            addr: addr.0.clone(),
+            op_name: tr
+                .map(|t| &t.op_name)
+                .or(mr.map(|m| &m.op_name))
+                .cloned()
+                .unwrap_or_default(),
+            scope,
+            activations: tr.map(|t| t.activations.clone()),
+            total_active_ms: tr.map(|t| t.total_active_ms.clone()),
+            batched_in: mr.map(|m| m.batched_in.clone()),
+            merges: mr.map(|m| m.merges.clone()),
+            merge_in: mr.map(|m| m.merge_in.clone()),
+            merge_out: mr.map(|m| m.merge_out.clone()),
+            dropped: mr.map(|m| m.dropped.clone()),
+        });
+    }
+    let mean_of = |s: &Option<Stats>| s.as_ref().map_or(0.0, |s| s.mean);
+    unmapped.sort_by(|a, b| {
+        mean_of(&b.total_active_ms)
+            .total_cmp(&mean_of(&a.total_active_ms))
+            .then_with(|| mean_of(&b.batched_in).total_cmp(&mean_of(&a.batched_in)))
+            .then_with(|| a.addr.cmp(&b.addr))
+    });
+
+    let total_mapped_ms = Stats::from_values(&total_workers.total_active_ms);
+    let dataflow_ms = dataflow_workers.map(|w| Stats::from_values(&w));
+    let coverage_pct = dataflow_ms
+        .as_ref()
+        .filter(|d| d.mean > 0.0)
+        .map(|d| total_mapped_ms.mean / d.mean * 100.0);
+    let total_unmapped_ms = Stats::from_values(&unmapped_ms_workers);
+    let unmapped_leaves: Vec<&UnmappedOperatorView> =
+        unmapped.iter().filter(|u| !u.scope).collect();
+    if let Some(first) = unmapped_leaves.first() {
+        diags.warning(
+            None,
+            format!(
+                "snapshot {}: {} logged operator(s) are not mapped by ops.json ({:.1} ms mean per worker), e.g. {:?} {}",
+                label,
+                unmapped_leaves.len(),
+                total_unmapped_ms.mean,
+                first.addr,
+                first.op_name
+            ),
+        );
+    }
+
+    // Phase 8: physical scope tree from the operator addresses.
+    let scopes = scope::build_scopes(
+        time,
+        memory,
+        |v| align_workers(v, time_workers, &workers),
+        |v| align_workers(v, memory_workers, &workers),
+        |addr| {
+            let name = owner.get(addr)?;
+            nodes_spec.get(*name).map(|s| s.block.as_str())
+        },
+    );
+
+    // Phase 9: rule and relation costs (shared nodes weighted by `shared_policy`).
+    let rule_nodes = rule_node_names(program);
+    let rules = build_rule_views(program, &rule_nodes, &nodes_view, shared_policy);
+    let relations = build_relation_views(program, &rule_nodes, &nodes_view, shared_policy);
+
     Ok(ReportData {
         roots,
         num_workers,
+        workers,
+        has_time: time_snapshot.is_some(),
+        has_memory: memory_snapshot.is_some(),
+        extra_metrics: extra_metrics.into_iter().collect(),
+        critical_paths,
         totals: TotalsView {
             names: nodes_spec.len(),
             operators_in_time: time.len(),
             operators_mapped,
             total_mapped_ms,
-            total_mapped_activations,
-            total_batched_in,
+            total_mapped_activations: Stats::from_values(&total_workers.activations),
+            total_batched_in: Stats::from_values(&total_workers.batched_in),
+            operators_unmapped: unmapped.len(),
+            total_unmapped_ms,
+            dataflow_ms,
+            coverage_pct,
         },
+        blocks,
+        rules,
+        relations,
         nodes: nodes_view,
-        rules: build_rule_views(rules_spec, nodes_spec, fingerprint_to_node),
+        unmapped,
+        scopes,
+        runs: None,
+        shared_policy,
     })
 }
 
+/// Names of the nodes each rule's plan maps to, by rule index.
+fn rule_node_names(program: &Program) -> Vec<BTreeSet<&str>> {
+    program
+        .rules
+        .iter()
+        .map(|rule| {
+            rule.nodes
+                .values()
+                .filter_map(|plan| program.nodes.get_key_value(&plan.node.to_string()))
+                .map(|(name, _)| name.as_str())
+                .collect()
+        })
+        .collect()
+}
+
 fn build_rule_views(
-    rules_spec: &[RuleSpec],
-    nodes_spec: &BTreeMap<String, NodeSpec>,
-    fingerprint_to_node: &BTreeMap<String, String>,
+    program: &Program,
+    rule_nodes: &[BTreeSet<&str>],
+    nodes: &BTreeMap<String, NameNodeView>,
+    shared_policy: SharedPolicy,
 ) -> Vec<RuleView> {
-    let mut fp_to_rules: BTreeMap<String, Vec<usize>> = BTreeMap::new();
-    for (rule_idx, rule) in rules_spec.iter().enumerate() {
-        for fp in rule.nodes.keys() {
-            fp_to_rules.entry(fp.clone()).or_default().push(rule_idx);
+    let (rules_spec, nodes_spec) = (&program.rules, &program.nodes);
+
+    // Rules (by index, ascending) using each node, for shared-cost attribution.
+    let mut node_rules: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    for (rule_idx, names) in rule_nodes.iter().enumerate() {
+        for name in names {
+            node_rules.entry(name).or_default().push(rule_idx);
         }
     }
 
@@ -333,23 +794,19 @@ fn build_rule_views(
 
         let mut nodes_view: BTreeMap<String, RulePlanNodeView> = BTreeMap::new();
         for (fp, node) in &rule.nodes {
-            let node_name = fingerprint_to_node.get(fp).cloned();
-            let label = node_name
-                .as_ref()
-                .and_then(|n| nodes_spec.get(n))
-                .map(|s| s.label.clone());
+            let node_name = node.node.to_string();
+            let label = nodes_spec.get(&node_name).map(|s| s.label.clone());
 
             let parent_list = parents.get(fp).cloned().unwrap_or_default();
-            let shared = fp_to_rules
-                .get(fp)
-                .map(|rules| rules.iter().any(|&idx| idx != rule_idx))
-                .unwrap_or(false);
+            let shared = node_rules
+                .get(node_name.as_str())
+                .is_some_and(|rules| rules.iter().any(|&idx| idx != rule_idx));
 
             nodes_view.insert(
                 fp.clone(),
                 RulePlanNodeView {
                     fingerprint: fp.clone(),
-                    node: node_name,
+                    node: Some(node_name),
                     label,
                     children: node.children.clone(),
                     parents: parent_list,
@@ -362,14 +819,531 @@ fn build_rule_views(
             text: rule.text.clone(),
             root: rule.root.clone(),
             nodes: nodes_view,
+            cost: attributed_cost(
+                rule_idx,
+                &rule_nodes[rule_idx],
+                &node_rules,
+                nodes,
+                shared_policy,
+            ),
         });
     }
 
     views
 }
 
+fn build_relation_views(
+    program: &Program,
+    rule_nodes: &[BTreeSet<&str>],
+    nodes: &BTreeMap<String, NameNodeView>,
+    shared_policy: SharedPolicy,
+) -> Vec<RelationView> {
+    let graph = &program.relations;
+    let mut derived: Vec<(&String, &Relation)> = graph
+        .relations
+        .iter()
+        .filter(|(_, r)| r.is_derived())
+        .collect();
+    derived.sort_by_key(|(_, r)| r.rules[0]);
+
+    // Nodes of each relation's rules, and the relations (by position in `derived`,
+    // ascending) using each node.
+    let relation_nodes: Vec<BTreeSet<&str>> = derived
+        .iter()
+        .map(|(_, r)| {
+            r.rules
+                .iter()
+                .flat_map(|&i| rule_nodes[i].iter().copied())
+                .collect()
+        })
+        .collect();
+    let mut node_relations: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    for (idx, names) in relation_nodes.iter().enumerate() {
+        for name in names {
+            node_relations.entry(name).or_default().push(idx);
+        }
+    }
+
+    derived
+        .iter()
+        .enumerate()
+        .map(|(idx, (name, relation))| RelationView {
+            relation: name.to_string(),
+            signature: graph.rules[relation.rules[0]]
+                .as_ref()
+                .map(|r| r.head.to_string())
+                .unwrap_or_default(),
+            rules: relation.rules.clone(),
+            depends_on: relation.depends_on.iter().cloned().collect(),
+            negated: relation.negated.iter().cloned().collect(),
+            inputs: graph.inputs(name).into_iter().map(str::to_string).collect(),
+            recursive: graph.is_recursive(name),
+            cost: attributed_cost(
+                idx,
+                &relation_nodes[idx],
+                &node_relations,
+                nodes,
+                shared_policy,
+            ),
+        })
+        .collect()
+}
+
+/// Weighted per-worker sum over the nodes of one owner (a rule or a relation);
+/// `node_owners` lists every owner using a node, and `SharedPolicy` gives the weights.
+fn attributed_cost(
+    owner: usize,
+    names: &BTreeSet<&str>,
+    node_owners: &BTreeMap<&str, Vec<usize>>,
+    nodes: &BTreeMap<String, NameNodeView>,
+    shared_policy: SharedPolicy,
+) -> RuleCost {
+    let mut series = WorkerSeries::default();
+    let mut shared_nodes = 0;
+    for name in names {
+        let users = node_owners.get(name).map_or(&[][..], Vec::as_slice);
+        if users.len() > 1 {
+            shared_nodes += 1;
+        }
+        let weight = match shared_policy {
+            SharedPolicy::Full => 1.0,
+            SharedPolicy::Split => 1.0 / users.len().max(1) as f64,
+            SharedPolicy::First if users.first() == Some(&owner) => 1.0,
+            SharedPolicy::First => 0.0,
+        };
+        let Some(view) = nodes.get(*name) else {
+            continue;
+        };
+        let scaled = |values: &[f64]| values.iter().map(|v| v * weight).collect::<Vec<f64>>();
+        stats::accumulate(&mut series.activations, &scaled(&view.workers.activations));
+        stats::accumulate(
+            &mut series.total_active_ms,
+            &scaled(&view.workers.total_active_ms),
+        );
+        stats::accumulate(&mut series.batched_in, &scaled(&view.workers.batched_in));
+    }
+    RuleCost {
+        nodes: names.len(),
+        shared_nodes,
+        activations: Stats::from_values(&series.activations),
+        total_active_ms: Stats::from_values(&series.total_active_ms),
+        batched_in: Stats::from_values(&series.batched_in),
+    }
+}
+
+/// All nodes reachable from `start` along `edges`, including `start` itself.
+fn cone<'a>(start: &'a str, edges: &BTreeMap<&'a str, Vec<&'a str>>) -> BTreeSet<&'a str> {
+    let mut seen = BTreeSet::from([start]);
+    let mut stack = vec![start];
+    while let Some(n) = stack.pop() {
+        for &next in edges.get(n).into_iter().flatten() {
+            if seen.insert(next) {
+                stack.push(next);
+            }
+        }
+    }
+    seen
+}
+
+fn inclusive_cost(cone: &BTreeSet<&str>, nodes: &BTreeMap<String, NameNodeView>) -> InclusiveCost {
+    let mut series = WorkerSeries::default();
+    for view in cone.iter().filter_map(|n| nodes.get(*n)) {
+        stats::accumulate(&mut series.activations, &view.workers.activations);
+        stats::accumulate(&mut series.total_active_ms, &view.workers.total_active_ms);
+        stats::accumulate(&mut series.batched_in, &view.workers.batched_in);
+    }
+    InclusiveCost {
+        nodes: cone.len(),
+        activations: Stats::from_values(&series.activations),
+        total_active_ms: Stats::from_values(&series.total_active_ms),
+        batched_in: Stats::from_values(&series.batched_in),
+    }
+}
+
+/// Heaviest path (sum of `weight`) from an input node to a node without children.
+/// Paths start only at inputs: other nodes without parents, such as the feedback
+/// node of a recursive stratum, are fed through the loop and are not sources. A
+/// spec without input nodes (no `input` block or `Input` tag) starts paths at every
+/// node without parents. Nodes are visited in topological order; nodes on a cycle
+/// (not expected in ops.json) are never reached and so never on the path.
+fn critical_path(
+    nodes: &BTreeMap<String, NameNodeView>,
+    parents: &BTreeMap<&str, Vec<&str>>,
+    children: &BTreeMap<&str, Vec<&str>>,
+    weight: impl Fn(&NameNodeView) -> f64,
+) -> CriticalPath {
+    let known = |n: &str| nodes.contains_key(n);
+    let roots: Vec<&NameNodeView> = nodes
+        .values()
+        .filter(|n| {
+            !parents
+                .get(n.name.as_str())
+                .is_some_and(|ps| ps.iter().any(|p| known(p)))
+        })
+        .collect();
+    let inputs: BTreeSet<&str> = roots
+        .iter()
+        .filter(|n| is_input(n))
+        .map(|n| n.name.as_str())
+        .collect();
+    let is_source = |n: &str| inputs.is_empty() || inputs.contains(n);
+
+    let mut pending: BTreeMap<&str, usize> = nodes
+        .keys()
+        .map(|n| {
+            let count = parents
+                .get(n.as_str())
+                .map_or(0, |ps| ps.iter().filter(|p| known(p)).count());
+            (n.as_str(), count)
+        })
+        .collect();
+    let mut ready: Vec<&str> = pending
+        .iter()
+        .filter(|(_, count)| **count == 0)
+        .map(|(n, _)| *n)
+        .collect();
+
+    // Heaviest path cost ending at each node, and the parent it comes through.
+    let mut best: BTreeMap<&str, (f64, Option<&str>)> = BTreeMap::new();
+    while let Some(n) = ready.pop() {
+        let via = parents
+            .get(n)
+            .into_iter()
+            .flatten()
+            .filter_map(|p| best.get(p).map(|(cost, _)| (*cost, *p)))
+            .max_by(|a, b| a.0.total_cmp(&b.0));
+        if via.is_some() || is_source(n) {
+            let w = nodes.get(n).map_or(0.0, &weight);
+            best.insert(n, (via.map_or(0.0, |v| v.0) + w, via.map(|v| v.1)));
+        }
+
+        for c in children.get(n).into_iter().flatten() {
+            if let Some(count) = pending.get_mut(c) {
+                *count -= 1;
+                if *count == 0 {
+                    ready.push(c);
+                }
+            }
+        }
+    }
+
+    let is_sink = |n: &str| {
+        !children
+            .get(n)
+            .is_some_and(|cs| cs.iter().any(|c| known(c)))
+    };
+    let Some((&sink, &(total_ms, _))) = best
+        .iter()
+        .filter(|(n, _)| is_sink(n))
+        .max_by(|a, b| a.1.0.total_cmp(&b.1.0))
+    else {
+        return CriticalPath::default();
+    };
+
+    let mut path = vec![sink];
+    while let Some(&(_, Some(prev))) = best.get(path[path.len() - 1]) {
+        path.push(prev);
+    }
+    path.reverse();
+
+    CriticalPath {
+        node_ms: path
+            .iter()
+            .map(|n| nodes.get(*n).map_or(0.0, &weight))
+            .collect(),
+        nodes: path.into_iter().map(String::from).collect(),
+        total_ms,
+        worker: None,
+    }
+}
+
+/// An input node: in an `input` block or tagged `Input`.
+fn is_input(node: &NameNodeView) -> bool {
+    node.block.to_lowercase().starts_with("input")
+        || node.tags.iter().any(|t| t.eq_ignore_ascii_case("input"))
+}
+
+/// Re-index per-worker `values` (ordered like `from`) onto the `to` worker list.
+/// Workers absent from `from` get 0.
+fn align_workers(values: &[f64], from: &[u32], to: &[u32]) -> Vec<f64> {
+    if from == to {
+        return values.to_vec();
+    }
+    to.iter()
+        .map(|w| {
+            from.iter()
+                .position(|f| f == w)
+                .and_then(|i| values.get(i))
+                .copied()
+                .unwrap_or(0.0)
+        })
+        .collect()
+}
+
 fn normalize_parents<T: Ord>(mut parents: Vec<T>) -> Vec<T> {
     parents.sort();
     parents.dedup();
     parents
 }
+
+// `program`, `time` and `report` are also the fixtures of the exporters' tests.
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::log::TimeRow;
+    use crate::ops::OpsSpec;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    pub(crate) fn program(ops: serde_json::Value) -> Program {
+        let spec: OpsSpec = serde_json::from_value(ops).unwrap();
+        let mut diags = Diagnostics::new(false);
+        let validated = spec.validate_and_build("ops.json", &mut diags).unwrap();
+        Program::new(validated, &mut diags)
+    }
+
+    /// A time snapshot of two workers (0 and 1): `(addr, op_name, ms per worker)`.
+    pub(crate) fn time(rows: &[(&[u32], &str, [f64; 2])]) -> Snapshot<TimeIndex> {
+        let data = rows
+            .iter()
+            .map(|(addr, op_name, ms)| {
+                let row = TimeRow {
+                    activations: Stats::from_values(&[1.0, 1.0]),
+                    total_active_ms: Stats::from_values(ms),
+                    activations_by_worker: vec![1.0, 1.0],
+                    total_active_ms_by_worker: ms.to_vec(),
+                    extra: BTreeMap::new(),
+                    op_name: op_name.to_string(),
+                    num_workers: 2,
+                };
+                (Addr::new(addr.to_vec()), row)
+            })
+            .collect();
+        Snapshot {
+            label: "t0".to_string(),
+            workers: vec![0, 1],
+            data,
+        }
+    }
+
+    pub(crate) fn report(
+        program: &Program,
+        time: &Snapshot<TimeIndex>,
+        policy: SharedPolicy,
+    ) -> ReportData {
+        build_report_data(
+            program,
+            Some(time),
+            None,
+            policy,
+            &mut Diagnostics::discard(),
+        )
+        .unwrap()
+    }
+
+    /// Mean time of each rule.
+    fn rule_ms(data: &ReportData) -> Vec<f64> {
+        data.rules
+            .iter()
+            .map(|r| r.cost.total_active_ms.mean)
+            .collect()
+    }
+
+    /// Two rules whose plans share node 0 (6 ms); node 1 (1 ms) and node 2 (2 ms)
+    /// are their own.
+    fn shared_node() -> (Program, Snapshot<TimeIndex>) {
+        let program = program(json!({
+            "nodes": [
+                { "id": 0, "block": "stratum 0", "fingerprint": "s", "operators": [[0, 1]] },
+                {
+                    "id": 1, "block": "stratum 0", "fingerprint": "a",
+                    "operators": [[0, 2]], "parents": [0],
+                },
+                {
+                    "id": 2, "block": "stratum 0", "fingerprint": "b",
+                    "operators": [[0, 3]], "parents": [0],
+                },
+            ],
+            "rules": [
+                {
+                    "text": "p(x) :- e(x).",
+                    "plan_tree": [
+                        { "fingerprint": "s" },
+                        { "fingerprint": "a", "parents": ["s"] },
+                    ],
+                },
+                {
+                    "text": "q(x) :- e(x).",
+                    "plan_tree": [
+                        { "fingerprint": "s" },
+                        { "fingerprint": "b", "parents": ["s"] },
+                    ],
+                },
+            ],
+        }));
+        let time = time(&[
+            (&[0, 1], "Scan", [4.0, 8.0]),
+            (&[0, 2], "Map", [1.0, 1.0]),
+            (&[0, 3], "Map", [2.0, 2.0]),
+        ]);
+        (program, time)
+    }
+
+    #[test]
+    fn split_policy_halves_a_shared_node() {
+        let (program, time) = shared_node();
+        let data = report(&program, &time, SharedPolicy::Split);
+        assert_eq!(rule_ms(&data), vec![4.0, 5.0]);
+        // Split costs add up to the mapped time.
+        assert_eq!(data.totals.total_mapped_ms.mean, 9.0);
+
+        let cost = &data.rules[0].cost;
+        assert_eq!((cost.nodes, cost.shared_nodes), (2, 1));
+        assert_eq!(
+            (cost.total_active_ms.min, cost.total_active_ms.max),
+            (3.0, 5.0)
+        );
+        assert!(data.rules[0].nodes["s"].shared);
+        assert!(!data.rules[0].nodes["a"].shared);
+    }
+
+    #[test]
+    fn first_policy_charges_a_shared_node_to_the_first_rule() {
+        let (program, time) = shared_node();
+        let data = report(&program, &time, SharedPolicy::First);
+        assert_eq!(rule_ms(&data), vec![7.0, 2.0]);
+    }
+
+    #[test]
+    fn full_policy_counts_a_shared_node_in_every_rule() {
+        let (program, time) = shared_node();
+        let data = report(&program, &time, SharedPolicy::Full);
+        assert_eq!(rule_ms(&data), vec![7.0, 8.0]);
+        // Relations are weighted like rules; here each relation has one rule.
+        let relations: Vec<f64> = data
+            .relations
+            .iter()
+            .map(|r| r.cost.total_active_ms.mean)
+            .collect();
+        assert_eq!(relations, vec![7.0, 8.0]);
+    }
+
+    #[test]
+    fn rule_costs_use_the_node_in_the_rule_block() {
+        // "x" names a node in each stratum; the rule lies in stratum 1 through "y".
+        let program = program(json!({
+            "nodes": [
+                { "id": 0, "block": "stratum 0", "fingerprint": "x", "operators": [[0, 1]] },
+                { "id": 1, "block": "stratum 1", "fingerprint": "x", "operators": [[0, 2]] },
+                {
+                    "id": 2, "block": "stratum 1", "fingerprint": "y",
+                    "operators": [[0, 3]], "parents": [1],
+                },
+            ],
+            "rules": [{
+                "text": "p(x) :- e(x).",
+                "plan_tree": [
+                    { "fingerprint": "x" },
+                    { "fingerprint": "y", "parents": ["x"] },
+                ],
+            }],
+        }));
+        let time = time(&[
+            (&[0, 1], "Scan", [100.0, 100.0]),
+            (&[0, 2], "Scan", [3.0, 3.0]),
+            (&[0, 3], "Map", [1.0, 1.0]),
+        ]);
+        let data = report(&program, &time, SharedPolicy::Split);
+
+        assert_eq!(data.rules[0].nodes["x"].node.as_deref(), Some("1"));
+        assert_eq!(rule_ms(&data), vec![4.0]);
+    }
+
+    /// A diamond 0 -> {1, 2} -> 3 from input node 0, plus node 4 without parents
+    /// (outside the input block, like a recursive stratum's feedback node) into 3.
+    fn diamond() -> ReportData {
+        let program = program(json!({
+            "nodes": [
+                { "id": 0, "block": "input", "tags": ["Input"], "operators": [[0, 1]] },
+                { "id": 1, "block": "stratum 0", "operators": [[0, 2]], "parents": [0] },
+                { "id": 2, "block": "stratum 0", "operators": [[0, 3]], "parents": [0] },
+                { "id": 3, "block": "stratum 0", "operators": [[0, 4]], "parents": [1, 2, 4] },
+                { "id": 4, "block": "stratum 0", "operators": [[0, 5]] },
+            ],
+        }));
+        let time = time(&[
+            (&[0, 1], "Input", [1.0, 1.0]),
+            (&[0, 2], "Join", [5.0, 4.0]),
+            (&[0, 3], "Join", [1.0, 7.0]),
+            (&[0, 4], "Concat", [1.0, 1.0]),
+            (&[0, 5], "Feedback", [50.0, 50.0]),
+        ]);
+        report(&program, &time, SharedPolicy::Split)
+    }
+
+    fn path_nodes(path: &CriticalPath) -> Vec<&str> {
+        path.nodes.iter().map(String::as_str).collect()
+    }
+
+    #[test]
+    fn critical_paths_start_at_inputs() {
+        let data = diamond();
+
+        // Node 4 is heavier than the whole diamond, but it is not a source.
+        let mean = &data.critical_paths.mean;
+        assert_eq!(path_nodes(mean), vec!["0", "1", "3"]);
+        assert_eq!(mean.node_ms, vec![1.0, 4.5, 1.0]);
+        assert_eq!((mean.total_ms, mean.worker), (6.5, None));
+
+        // Worker 1 spends 7 ms in node 2: its own path (9 ms) is the heaviest.
+        let slowest = &data.critical_paths.max_worker;
+        assert_eq!(path_nodes(slowest), vec!["0", "2", "3"]);
+        assert_eq!(slowest.node_ms, vec![1.0, 7.0, 1.0]);
+        assert_eq!((slowest.total_ms, slowest.worker), (9.0, Some(1)));
+    }
+
+    #[test]
+    fn inclusive_costs_count_each_node_of_the_cone_once() {
+        let data = diamond();
+        let cost = |name: &str, cone: fn(&NameNodeView) -> &InclusiveCost| {
+            let c = cone(&data.nodes[name]);
+            (c.nodes, c.total_active_ms.mean)
+        };
+
+        // Node 3 is reached through both 1 and 2.
+        assert_eq!(cost("0", |n| &n.downstream), (4, 10.5));
+        assert_eq!(cost("2", |n| &n.downstream), (2, 5.0));
+        assert_eq!(cost("3", |n| &n.upstream), (5, 60.5));
+        assert_eq!(cost("1", |n| &n.upstream), (2, 5.5));
+        // The primary tree hangs 3 under its first parent, 1.
+        assert_eq!(cost("0", |n| &n.subtree), (4, 10.5));
+        assert_eq!(cost("1", |n| &n.subtree), (2, 5.5));
+        assert_eq!(cost("2", |n| &n.subtree), (1, 4.0));
+        assert_eq!(cost("4", |n| &n.subtree), (1, 50.0));
+    }
+
+    #[test]
+    fn unmapped_operators_are_listed_and_lower_coverage() {
+        let program = program(json!({
+            "nodes": [{ "id": 0, "block": "stratum 0", "operators": [[0, 1]] }],
+        }));
+        let time = time(&[
+            (&[0], "Dataflow", [10.0, 10.0]),
+            (&[0, 1], "Join", [4.0, 4.0]),
+            (&[0, 2], "Exchange", [1.0, 3.0]),
+        ]);
+        let data = report(&program, &time, SharedPolicy::Split);
+
+        let unmapped: Vec<_> = data
+            .unmapped
+            .iter()
+            .map(|u| (u.addr.clone(), u.op_name.as_str(), u.scope))
+            .collect();
+        assert_eq!(unmapped, vec![(vec![0, 2], "Exchange", false)]);
+        let totals = &data.totals;
+        assert_eq!((totals.operators_mapped, totals.operators_unmapped), (1, 1));
+        assert_eq!(totals.total_unmapped_ms.mean, 2.0);
+        assert_eq!(totals.dataflow_ms.as_ref().map(|d| d.mean), Some(10.0));
+        assert_eq!(totals.coverage_pct, Some(40.0));
+    }
+}